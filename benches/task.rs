@@ -0,0 +1,87 @@
+//! Microbenchmarks for the cost of spawning and waking tasks.
+//!
+//! Run with `cargo bench --bench task`. No harness is used, so the benchmarks
+//! also run under a WASI runtime.
+
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration, Instant};
+use wasmedge_async::{spawn_local, yield_now, Executor};
+
+const ITERATIONS: u32 = 100_000;
+
+fn report(name: &str, elapsed: Duration, ops: u32) {
+    println!(
+        "{:<24} {:>10.1} ns/op",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64
+    );
+}
+
+/// Spawns tasks that complete on their first poll.
+fn spawn() {
+    let mut executor = Executor::new();
+    let elapsed = executor
+        .block_on(|| async {
+            let start = Instant::now();
+            let handles: Vec<_> = (0..ITERATIONS).map(|_| spawn_local(async {})).collect();
+            for handle in handles {
+                handle.await.unwrap();
+            }
+            start.elapsed()
+        })
+        .unwrap();
+    report("spawn", elapsed, ITERATIONS);
+}
+
+/// A task waking itself, going through the run queue each time.
+fn yield_self() {
+    let mut executor = Executor::new();
+    let elapsed = executor
+        .block_on(|| async {
+            let start = Instant::now();
+            spawn_local(async {
+                for _ in 0..ITERATIONS {
+                    yield_now().await;
+                }
+            })
+            .await
+            .unwrap();
+            start.elapsed()
+        })
+        .unwrap();
+    report("wake (yield)", elapsed, ITERATIONS);
+}
+
+/// Two tasks waking each other, which runs the woken task from the LIFO slot.
+fn ping_pong() {
+    let mut executor = Executor::new();
+    let elapsed = executor
+        .block_on(|| async {
+            let (mut ping_tx, mut ping_rx) = mpsc::channel::<u32>(1);
+            let (mut pong_tx, mut pong_rx) = mpsc::channel::<u32>(1);
+            let start = Instant::now();
+            let pong = spawn_local(async move {
+                while let Some(n) = ping_rx.next().await {
+                    pong_tx.send(n).await.unwrap();
+                }
+            });
+            let ping = spawn_local(async move {
+                for n in 0..ITERATIONS {
+                    ping_tx.send(n).await.unwrap();
+                    pong_rx.next().await.unwrap();
+                }
+            });
+            ping.await.unwrap();
+            pong.await.unwrap();
+            start.elapsed()
+        })
+        .unwrap();
+    report("wake (ping-pong)", elapsed, ITERATIONS * 2);
+}
+
+fn main() {
+    spawn();
+    yield_self();
+    ping_pong();
+}
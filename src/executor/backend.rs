@@ -0,0 +1,117 @@
+//! The interface between the reactor and the IO facilities of the host.
+
+use super::Interest;
+use std::io;
+use std::os::fd::RawFd;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(target_os = "wasi")]
+use wasmedge_wasi_socket::poll::{poll, EventType, Subscription, SystemTimestamp};
+
+/// Userdata of the timeout subscription, never a valid fd.
+#[cfg(target_os = "wasi")]
+const TIMEOUT_TOKEN: u64 = u64::MAX;
+
+/// Waits for fds to become ready on behalf of a [`Reactor`](super::Reactor).
+///
+/// The reactor keeps track of the tasks waiting on each fd, and on every
+/// turn asks the backend to wait for the directions that tasks wait on. A
+/// backend is shared by all threads of a multi-threaded runtime, which call
+/// [`Backend::poll`] without holding the reactor.
+pub trait Backend: Send + Sync {
+    /// Starts tracking `fd`. The reactor only waits on registered fds.
+    fn register(&self, fd: RawFd) -> io::Result<()>;
+
+    /// Stops tracking `fd`.
+    fn deregister(&self, fd: RawFd);
+
+    /// Waits until one of `interests` is ready, or for at most `timeout` if
+    /// given, and returns the ready fds along with the ready directions.
+    ///
+    /// A zero timeout only collects the fds that are ready already.
+    fn poll(
+        &self,
+        interests: &[(RawFd, Interest)],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(RawFd, Interest)>>;
+
+    /// Returns true if tasks may be queued by other threads while the backend
+    /// waits, so that the reactor has to interrupt its waits. Defaults to
+    /// true.
+    fn needs_wakeup(&self) -> bool {
+        true
+    }
+}
+
+/// The backend of WasmEdge sockets, waiting with `poll_oneoff`.
+#[cfg(target_os = "wasi")]
+#[derive(Debug, Default)]
+pub struct WasmEdgeBackend;
+
+#[cfg(target_os = "wasi")]
+impl Backend for WasmEdgeBackend {
+    fn register(&self, _fd: RawFd) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn deregister(&self, _fd: RawFd) {}
+
+    fn poll(
+        &self,
+        interests: &[(RawFd, Interest)],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(RawFd, Interest)>> {
+        let mut subs = interests
+            .iter()
+            .map(|&(fd, interest)| Subscription::IO {
+                userdata: fd as u64,
+                fd,
+                read_event: interest != Interest::Write,
+                write_event: interest != Interest::Read,
+            })
+            .collect::<Vec<Subscription>>();
+        if let Some(timeout) = timeout {
+            subs.push(Subscription::Timeout {
+                userdata: TIMEOUT_TOKEN,
+                timeout: SystemTimestamp {
+                    timeout: timeout.as_nanos() as u64,
+                    precision: 0,
+                },
+            });
+        }
+        let mut ready = Vec::new();
+        for event in poll(&subs)? {
+            let interest = match event.event_type {
+                EventType::Read => Interest::Read,
+                EventType::Write => Interest::Write,
+                EventType::Timeout => continue,
+                EventType::Error(e) => return Err(e),
+            };
+            ready.push((event.userdata as RawFd, interest));
+        }
+        Ok(ready)
+    }
+
+    /// Without the `multi-thread` feature, tasks are only ever queued by the
+    /// thread that waits, so waits never need to be interrupted.
+    fn needs_wakeup(&self) -> bool {
+        cfg!(feature = "multi-thread")
+    }
+}
+
+/// Returns the backend reactors use unless configured otherwise.
+#[cfg(target_os = "wasi")]
+pub(crate) fn default_backend() -> Arc<dyn Backend> {
+    Arc::new(WasmEdgeBackend)
+}
+
+/// Returns the backend reactors use unless configured otherwise.
+///
+/// # Panics
+///
+/// Panics if no epoll instance can be created, e.g. because the process ran
+/// out of fds.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn default_backend() -> Arc<dyn Backend> {
+    Arc::new(super::EpollBackend::new().expect("failed to create an epoll instance"))
+}
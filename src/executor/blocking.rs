@@ -0,0 +1,127 @@
+use super::raw::TaskRef;
+use super::{JoinHandle, PanicPolicy};
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::task::Context;
+use std::thread;
+use std::time::Duration;
+
+/// Maximum number of threads of the blocking pool.
+const MAX_THREADS: usize = 16;
+
+/// How long an idle thread of the blocking pool waits for a new job before it
+/// exits.
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    state: Mutex<PoolState>,
+    condvar: Condvar,
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| Pool {
+        state: Mutex::new(PoolState {
+            jobs: VecDeque::new(),
+            threads: 0,
+            idle: 0,
+        }),
+        condvar: Condvar::new(),
+    })
+}
+
+fn lock(pool: &Pool) -> MutexGuard<'_, PoolState> {
+    // Jobs run without the lock held, so it is never poisoned by a panic.
+    pool.state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs the blocking function `f` on a thread pool and returns a
+/// [`JoinHandle`] resolving to its result.
+///
+/// Compression, hashing or synchronous library calls would stall every task
+/// of the executor; `spawn_blocking` moves them off the executor thread. The
+/// awaiting task is woken through its executor once `f` returns. A panic in
+/// `f` is returned as a [`JoinError`](super::JoinError).
+///
+/// The pool starts threads on demand, up to 16, and lets them exit after being
+/// idle for 10 seconds. Aborting the `JoinHandle` only prevents `f` from
+/// starting; a running function cannot be interrupted.
+///
+/// Requires the `multi-thread` feature and thread support, i.e. wasi-threads
+/// or a native target.
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    // Without a scheduler, the task is only polled by the job, which lets an
+    // aborted task complete as cancelled without calling `f`.
+    let (task, join_handle) =
+        TaskRef::new(async move { f() }, None, PanicPolicy::IsolateTask, None);
+    submit(Box::new(move || {
+        let waker = task.waker_ref();
+        // SAFETY: the task is polled only here, and its future is `Send`. It
+        // never waits, so it completes in a single poll.
+        let _ = unsafe { task.poll(&mut Context::from_waker(&waker)) };
+    }));
+    join_handle
+}
+
+fn submit(job: Job) {
+    let pool = pool();
+    let mut state = lock(pool);
+    state.jobs.push_back(job);
+    if state.idle > 0 {
+        pool.condvar.notify_one();
+        return;
+    }
+    if state.threads >= MAX_THREADS {
+        return;
+    }
+    state.threads += 1;
+    drop(state);
+    let spawned = thread::Builder::new()
+        .name("wasmedge-async-blocking".to_string())
+        .spawn(move || work(pool));
+    if let Err(e) = spawned {
+        let mut state = lock(pool);
+        state.threads -= 1;
+        // Without any thread the job would never run.
+        assert!(
+            state.threads > 0,
+            "failed to spawn a blocking thread: {}",
+            e
+        );
+    }
+}
+
+fn work(pool: &Pool) {
+    let mut state = lock(pool);
+    loop {
+        if let Some(job) = state.jobs.pop_front() {
+            drop(state);
+            job();
+            state = lock(pool);
+            continue;
+        }
+        state.idle += 1;
+        let (next, timeout) = pool
+            .condvar
+            .wait_timeout(state, KEEP_ALIVE)
+            .unwrap_or_else(|e| e.into_inner());
+        state = next;
+        state.idle -= 1;
+        if timeout.timed_out() && state.jobs.is_empty() {
+            state.threads -= 1;
+            return;
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use super::backend::{self, Backend};
+use super::coop::DEFAULT_BUDGET;
+use super::metrics::ExecutorMetrics;
+use super::Histogram;
+use super::{
+    Executor, Handle, Reactor, Shared, TaskQueue, DEFAULT_TASKS_PER_TURN, DEFAULT_TASK_QUEUE_SIZE,
+};
+use crate::task::TaskInfo;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type TaskCallback = Box<dyn Fn(&TaskInfo) + 'static>;
+type Callback = Box<dyn Fn() + 'static>;
+type SlowPollCallback = Box<dyn Fn(&TaskInfo, Duration) + 'static>;
+
+/// How the executor reacts to a panic in a spawned task.
+///
+/// Catching panics requires the program to be built with `panic = "unwind"`;
+/// with `panic = "abort"` any panic terminates the program regardless of the
+/// policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The panic is caught and returned as a [`JoinError`](super::JoinError)
+    /// by the task's [`JoinHandle`](super::JoinHandle). Other tasks keep
+    /// running.
+    IsolateTask,
+    /// The panic unwinds out of [`Executor::block_on`], tearing down the
+    /// executor.
+    AbortRuntime,
+}
+
+/// Settings of an executor that are fixed once it is built.
+pub(crate) struct Config {
+    pub(crate) tasks_per_turn: usize,
+    pub(crate) task_budget: usize,
+    pub(crate) panic_policy: PanicPolicy,
+    pub(crate) max_tasks: Option<usize>,
+    pub(crate) on_task_spawn: Option<TaskCallback>,
+    pub(crate) on_task_terminate: Option<TaskCallback>,
+    pub(crate) before_park: Option<Callback>,
+    pub(crate) after_unpark: Option<Callback>,
+    pub(crate) slow_poll: Option<(Duration, SlowPollCallback)>,
+}
+
+/// Configures and creates an [`Executor`].
+///
+/// ```ignore
+/// let mut executor = Executor::builder()
+///     .task_queue_capacity(256)
+///     .on_task_spawn(|task| println!("spawned {}", task))
+///     .build();
+/// ```
+pub struct Builder {
+    task_queue_capacity: usize,
+    max_events_per_turn: usize,
+    backend: Option<Arc<dyn Backend>>,
+    poll_time_histogram: bool,
+    config: Config,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            task_queue_capacity: DEFAULT_TASK_QUEUE_SIZE,
+            max_events_per_turn: usize::MAX,
+            backend: None,
+            poll_time_histogram: false,
+            config: Config {
+                tasks_per_turn: DEFAULT_TASKS_PER_TURN,
+                task_budget: DEFAULT_BUDGET,
+                panic_policy: PanicPolicy::IsolateTask,
+                max_tasks: None,
+                on_task_spawn: None,
+                on_task_terminate: None,
+                before_park: None,
+                after_unpark: None,
+                slow_poll: None,
+            },
+        }
+    }
+
+    /// Sets the number of tasks the run queue can hold before it reallocates.
+    pub fn task_queue_capacity(mut self, capacity: usize) -> Self {
+        self.task_queue_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum number of IO events dispatched per reactor turn.
+    pub fn max_events_per_turn(mut self, max: usize) -> Self {
+        self.max_events_per_turn = max;
+        self
+    }
+
+    /// Sets the backend the reactor waits for IO with. Defaults to
+    /// `WasmEdgeBackend` on WASI and `EpollBackend` elsewhere.
+    pub fn backend(mut self, backend: impl Backend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Sets how many tasks are polled before the reactor is polled again.
+    ///
+    /// When tasks are still runnable after that many polls, the reactor only
+    /// collects ready events and does not block. Defaults to 61.
+    pub fn tasks_per_turn(mut self, n: usize) -> Self {
+        self.config.tasks_per_turn = n.max(1);
+        self
+    }
+
+    /// Sets how many IO operations a task may perform each time it is polled.
+    ///
+    /// Once the budget is spent, IO futures return `Pending` and the task is
+    /// rescheduled, so that a task working on always-ready IO cannot starve
+    /// the others.
+    pub fn task_budget(mut self, budget: usize) -> Self {
+        self.config.task_budget = budget.max(1);
+        self
+    }
+
+    /// Sets how a panic in a spawned task is handled. Defaults to
+    /// [`PanicPolicy::IsolateTask`].
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.config.panic_policy = policy;
+        self
+    }
+
+    /// Limits the number of live tasks for [`try_spawn`](super::try_spawn)
+    /// and [`spawn_with_permit`](super::spawn_with_permit).
+    ///
+    /// Tasks spawned with [`spawn`](super::spawn) always start, but count
+    /// towards the limit. Unlimited by default.
+    pub fn max_tasks(mut self, max: usize) -> Self {
+        self.config.max_tasks = Some(max);
+        self
+    }
+
+    /// Enables recording how long each task poll takes, reported by
+    /// [`RuntimeMetrics::poll_time_histogram`](super::RuntimeMetrics::poll_time_histogram).
+    ///
+    /// This reads the clock twice per poll, so it is off by default.
+    pub fn enable_poll_time_histogram(mut self) -> Self {
+        self.poll_time_histogram = true;
+        self
+    }
+
+    /// Sets a callback invoked whenever a task is spawned.
+    pub fn on_task_spawn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&TaskInfo) + 'static,
+    {
+        self.config.on_task_spawn = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback invoked whenever a task completes, including tasks
+    /// that are aborted or cancelled when the executor is dropped, so that it
+    /// is called once for every task passed to [`Builder::on_task_spawn`].
+    pub fn on_task_terminate<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&TaskInfo) + 'static,
+    {
+        self.config.on_task_terminate = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback invoked before the executor blocks waiting for IO.
+    pub fn before_park<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        self.config.before_park = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback invoked after the executor wakes up from waiting for IO.
+    pub fn after_unpark<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + 'static,
+    {
+        self.config.after_unpark = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback invoked whenever polling a task takes at least
+    /// `threshold`.
+    ///
+    /// A poll that long blocks every other task and the reactor, e.g. because
+    /// the task called a blocking function or did heavy computation. The
+    /// callback receives the task and the duration of the poll.
+    pub fn on_slow_poll<F>(mut self, threshold: Duration, f: F) -> Self
+    where
+        F: Fn(&TaskInfo, Duration) + 'static,
+    {
+        self.config.slow_poll = Some((threshold, Box::new(f)));
+        self
+    }
+
+    pub fn build(self) -> Executor {
+        let backend = self.backend.unwrap_or_else(backend::default_backend);
+        let reactor = Reactor::with_backend(backend, self.max_events_per_turn);
+        let mut tasks = TaskQueue::new_with_capacity(self.task_queue_capacity);
+        tasks.wakeup = reactor.wakeup();
+        Executor {
+            handle: Handle::new(Shared {
+                tasks: Arc::new(tasks),
+                owned: RefCell::new(HashMap::new()),
+                capacity_waiters: RefCell::new(Vec::new()),
+                next_task_id: Cell::new(0),
+                reactor: Arc::new(Mutex::new(reactor)),
+                config: self.config,
+                metrics: ExecutorMetrics::default(),
+                poll_time_histogram: self
+                    .poll_time_histogram
+                    .then(|| RefCell::new(Histogram::default())),
+            }),
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
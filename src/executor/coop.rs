@@ -0,0 +1,89 @@
+//! Cooperative scheduling budget.
+//!
+//! Every task is given a budget of IO operations each time it is polled. Once
+//! the budget is spent, IO futures return `Poll::Pending` and reschedule the
+//! task, even if the underlying resource is ready, so a task reading from an
+//! always-ready source cannot starve the other tasks and the reactor.
+//!
+//! The budget is charged by the IO resources themselves: TCP sockets and the
+//! in-memory readers and writers.
+
+use std::cell::Cell;
+use std::task::{Context, Poll};
+
+pub(crate) const DEFAULT_BUDGET: usize = 128;
+
+thread_local! {
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with a fresh budget of `budget` operations.
+pub(crate) fn with_budget<R>(budget: usize, f: impl FnOnce() -> R) -> R {
+    struct ResetGuard(Option<usize>);
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            BUDGET.with(|cell| cell.set(self.0));
+        }
+    }
+
+    let prev = BUDGET.with(|cell| cell.replace(Some(budget)));
+    let _guard = ResetGuard(prev);
+    f()
+}
+
+/// Consumes one unit of the current task's budget.
+///
+/// Returns `Poll::Pending` and wakes the task if the budget is spent. The unit
+/// is given back when the returned guard is dropped without calling
+/// [`RestoreOnPending::made_progress`], i.e. when the operation itself turned
+/// out to be pending.
+pub(crate) fn poll_proceed(cx: &mut Context<'_>) -> Poll<RestoreOnPending> {
+    BUDGET.with(|cell| match cell.get() {
+        None => Poll::Ready(RestoreOnPending(Cell::new(None))),
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            cell.set(Some(n - 1));
+            Poll::Ready(RestoreOnPending(Cell::new(Some(n))))
+        }
+    })
+}
+
+/// Runs the IO operation `f` if the current task's budget allows it, and
+/// charges one unit if the operation completes.
+///
+/// Resources call this from their `poll_*` methods, so that every caller is
+/// budgeted, whether it uses the extension traits or polls directly.
+pub(crate) fn poll_op<T>(
+    cx: &mut Context<'_>,
+    f: impl FnOnce(&mut Context<'_>) -> Poll<T>,
+) -> Poll<T> {
+    let coop = match poll_proceed(cx) {
+        Poll::Ready(coop) => coop,
+        Poll::Pending => return Poll::Pending,
+    };
+    let ret = f(cx);
+    if ret.is_ready() {
+        coop.made_progress();
+    }
+    ret
+}
+
+pub(crate) struct RestoreOnPending(Cell<Option<usize>>);
+
+impl RestoreOnPending {
+    pub(crate) fn made_progress(&self) {
+        self.0.set(None);
+    }
+}
+
+impl Drop for RestoreOnPending {
+    fn drop(&mut self) {
+        if let Some(budget) = self.0.get() {
+            BUDGET.with(|cell| cell.set(Some(budget)));
+        }
+    }
+}
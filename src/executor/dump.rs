@@ -0,0 +1,109 @@
+use crate::task::TaskInfo;
+use std::fmt;
+use std::os::fd::RawFd;
+use std::time::Instant;
+
+/// A snapshot of the live tasks of an executor, returned by
+/// [`Handle::dump`](super::Handle::dump).
+///
+/// The `Display` implementation prints one task per line.
+#[derive(Clone, Debug)]
+pub struct Dump {
+    tasks: Vec<TaskDump>,
+}
+
+impl Dump {
+    pub(crate) fn new(tasks: Vec<TaskDump>) -> Self {
+        Self { tasks }
+    }
+
+    /// Returns the live tasks, ordered by id.
+    pub fn tasks(&self) -> &[TaskDump] {
+        &self.tasks
+    }
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for task in &self.tasks {
+            writeln!(f, "{}: {}", task.info, task.state)?;
+        }
+        Ok(())
+    }
+}
+
+/// A live task in a [`Dump`].
+#[derive(Clone, Debug)]
+pub struct TaskDump {
+    info: TaskInfo,
+    state: TaskState,
+}
+
+impl TaskDump {
+    pub(crate) fn new(info: TaskInfo, state: TaskState) -> Self {
+        Self { info, state }
+    }
+
+    /// Returns the id and name of the task.
+    pub fn info(&self) -> &TaskInfo {
+        &self.info
+    }
+
+    /// Returns what the task was doing when the dump was taken.
+    pub fn state(&self) -> &TaskState {
+        &self.state
+    }
+}
+
+/// The state of a task in a [`Dump`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    /// The task is being polled; it is the one that took the dump.
+    Running,
+    /// The task is in the run queue.
+    Queued,
+    /// The task is waiting for the reactor.
+    Parked(Vec<WaitingOn>),
+    /// The task is waiting for something outside of the reactor, e.g. another
+    /// task or a channel.
+    Idle,
+}
+
+/// What a parked task is waiting on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitingOn {
+    /// The fd to become readable.
+    Read(RawFd),
+    /// The fd to become writable.
+    Write(RawFd),
+    /// A timer to fire at the deadline.
+    Timer(Instant),
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskState::Running => write!(f, "running"),
+            TaskState::Queued => write!(f, "queued"),
+            TaskState::Parked(waiting_on) => {
+                write!(f, "parked on ")?;
+                for (i, w) in waiting_on.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match w {
+                        WaitingOn::Read(fd) => write!(f, "read fd {}", fd)?,
+                        WaitingOn::Write(fd) => write!(f, "write fd {}", fd)?,
+                        WaitingOn::Timer(deadline) => write!(
+                            f,
+                            "timer in {:?}",
+                            deadline.saturating_duration_since(crate::time::now())
+                        )?,
+                    }
+                }
+                Ok(())
+            }
+            TaskState::Idle => write!(f, "idle"),
+        }
+    }
+}
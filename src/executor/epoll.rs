@@ -0,0 +1,147 @@
+//! A backend for native Linux, so that the runtime and the programs using it
+//! run and test on the host over std sockets.
+
+use super::{Backend, Interest};
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How many events one wait collects at most.
+const MAX_EVENTS: usize = 1024;
+
+/// The backend of native sockets, waiting with epoll.
+///
+/// Every fd is registered in one-shot mode: it is armed for the directions a
+/// task waits on, and disarmed once it is reported until a task waits on it
+/// again. A socket that nobody reads therefore cannot end every wait, even
+/// once its peer has hung up.
+#[derive(Debug)]
+pub struct EpollBackend {
+    epoll: OwnedFd,
+    /// The events each registered fd is armed for, or `None` if it was
+    /// reported and is disarmed.
+    armed: Mutex<HashMap<RawFd, Option<u32>>>,
+}
+
+impl EpollBackend {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: plain syscall, the returned fd is owned from here on.
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            // SAFETY: `epoll` is a fresh fd owned by nobody else.
+            epoll: unsafe { OwnedFd::from_raw_fd(epoll) },
+            armed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<RawFd, Option<u32>>> {
+        self.armed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ctl(&self, op: libc::c_int, fd: RawFd, events: u32) -> io::Result<()> {
+        let mut event = libc::epoll_event {
+            events: events | libc::EPOLLONESHOT as u32,
+            u64: fd as u64,
+        };
+        // SAFETY: `event` outlives the call.
+        if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Backend for EpollBackend {
+    fn register(&self, fd: RawFd) -> io::Result<()> {
+        self.ctl(libc::EPOLL_CTL_ADD, fd, 0)?;
+        self.lock().insert(fd, Some(0));
+        Ok(())
+    }
+
+    fn deregister(&self, fd: RawFd) {
+        if self.lock().remove(&fd).is_some() {
+            // Fails if the fd was closed already, which removes it as well.
+            let _ = self.ctl(libc::EPOLL_CTL_DEL, fd, 0);
+        }
+    }
+
+    fn poll(
+        &self,
+        interests: &[(RawFd, Interest)],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(RawFd, Interest)>> {
+        {
+            let mut armed = self.lock();
+            for &(fd, interest) in interests {
+                let events = match interest {
+                    Interest::Read => libc::EPOLLIN | libc::EPOLLRDHUP,
+                    Interest::Write => libc::EPOLLOUT,
+                    Interest::All => libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLOUT,
+                } as u32;
+                match armed.get_mut(&fd) {
+                    Some(current) if *current != Some(events) => {
+                        self.ctl(libc::EPOLL_CTL_MOD, fd, events)?;
+                        *current = Some(events);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let timeout = match timeout {
+            // Rounded up, so that a timer is never polled just before it fires.
+            Some(timeout) => timeout
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut events = Vec::<libc::epoll_event>::with_capacity(MAX_EVENTS);
+        // SAFETY: the kernel writes at most `MAX_EVENTS` events into the
+        // spare capacity, and returns how many it wrote.
+        let n = unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as libc::c_int,
+                timeout,
+            )
+        };
+        if n < 0 {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::Interrupted => Ok(Vec::new()),
+                _ => Err(error),
+            };
+        }
+        // SAFETY: see above.
+        unsafe { events.set_len(n as usize) };
+        let mut armed = self.lock();
+        let mut ready = Vec::with_capacity(events.len());
+        for event in events {
+            let (flags, fd) = (event.events, event.u64 as RawFd);
+            // A one-shot fd is disarmed once reported; an fd deregistered
+            // while waiting is skipped.
+            let wanted = match armed.get_mut(&fd).and_then(Option::take) {
+                Some(wanted) => wanted,
+                None => continue,
+            };
+            let closed = flags & (libc::EPOLLERR | libc::EPOLLHUP) as u32 != 0;
+            let readable = wanted & libc::EPOLLIN as u32 != 0
+                && (closed || flags & (libc::EPOLLIN | libc::EPOLLRDHUP) as u32 != 0);
+            let writable = wanted & libc::EPOLLOUT as u32 != 0
+                && (closed || flags & libc::EPOLLOUT as u32 != 0);
+            match (readable, writable) {
+                (true, true) => ready.push((fd, Interest::All)),
+                (true, false) => ready.push((fd, Interest::Read)),
+                (false, true) => ready.push((fd, Interest::Write)),
+                (false, false) => {}
+            }
+        }
+        Ok(ready)
+    }
+}
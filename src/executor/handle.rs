@@ -0,0 +1,138 @@
+use super::{Dump, JoinHandle, Priority, Reactor, RuntimeMetrics, Shared};
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Handle>> = const { RefCell::new(None) };
+}
+
+/// A cloneable reference to an [`Executor`](super::Executor).
+///
+/// A `Handle` can be captured by libraries to spawn tasks later, and entered
+/// with [`Handle::enter`] so that sockets can be created before
+/// [`Executor::block_on`](super::Executor::block_on) is called.
+#[derive(Clone)]
+pub struct Handle {
+    pub(crate) shared: Rc<Shared>,
+}
+
+/// Guard returned by [`Handle::enter`].
+///
+/// The previously entered handle, if any, is restored when the guard is
+/// dropped.
+pub struct EnterGuard<'a> {
+    prev: Option<Handle>,
+    _handle: PhantomData<&'a Handle>,
+}
+
+impl Handle {
+    pub(crate) fn new(shared: Shared) -> Self {
+        Self {
+            shared: Rc::new(shared),
+        }
+    }
+
+    /// Returns a handle to the executor entered on this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of [`Executor::block_on`] or [`Handle::enter`].
+    ///
+    /// [`Executor::block_on`]: super::Executor::block_on
+    pub fn current() -> Self {
+        with_current(Handle::clone)
+    }
+
+    /// Returns a handle to the executor entered on this thread, or an error if
+    /// there is none.
+    pub fn try_current() -> io::Result<Self> {
+        CONTEXT
+            .with(|ctx| ctx.borrow().clone())
+            .ok_or_else(no_executor)
+    }
+
+    /// Makes this executor the current one until the returned guard is
+    /// dropped.
+    pub fn enter(&self) -> EnterGuard<'_> {
+        let prev = CONTEXT.with(|ctx| ctx.borrow_mut().replace(self.clone()));
+        EnterGuard {
+            prev,
+            _handle: PhantomData,
+        }
+    }
+
+    /// Spawns a future onto this executor.
+    ///
+    /// The task starts running once the executor is driven by `block_on`.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn_local(future, None, Priority::Normal)
+    }
+
+    /// Spawns a future that is not `Send` onto this executor.
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.shared.spawn_local(future, None, Priority::Normal)
+    }
+}
+
+impl Handle {
+    /// Returns a snapshot of the executor's metrics.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        self.shared.metrics()
+    }
+
+    /// Returns a snapshot of every live task and what it is waiting on.
+    pub fn dump(&self) -> Dump {
+        self.shared.dump()
+    }
+}
+
+impl Drop for EnterGuard<'_> {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = prev);
+    }
+}
+
+fn no_executor() -> io::Error {
+    io::Error::other(
+        "there is no executor running, must be called from the context of `Executor::block_on` or `Handle::enter`",
+    )
+}
+
+/// Runs `f` with the current handle.
+///
+/// Panics if there is no current handle.
+pub(crate) fn with_current<R>(f: impl FnOnce(&Handle) -> R) -> R {
+    CONTEXT.with(|ctx| match ctx.borrow().as_ref() {
+        Some(handle) => f(handle),
+        None => panic!("{}", no_executor()),
+    })
+}
+
+/// Returns the reactor of the current handle.
+///
+/// With the `multi-thread` feature, falls back to the reactor of the
+/// multi-threaded runtime entered on this thread.
+///
+/// Panics if there is neither.
+pub(crate) fn current_reactor() -> Arc<Mutex<Reactor>> {
+    match Handle::try_current() {
+        Ok(handle) => handle.shared.reactor.clone(),
+        #[cfg(feature = "multi-thread")]
+        Err(e) => super::multi_thread::current_reactor().unwrap_or_else(|| panic!("{}", e)),
+        #[cfg(not(feature = "multi-thread"))]
+        Err(e) => panic!("{}", e),
+    }
+}
@@ -0,0 +1,156 @@
+use super::raw::TaskRef;
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An owned permission to await the output of a spawned task.
+///
+/// Dropping a `JoinHandle` detaches the task, which keeps running in the
+/// background. Use [`JoinHandle::abort`] to cancel it instead.
+pub struct JoinHandle<T> {
+    raw: TaskRef,
+    _output: PhantomData<T>,
+}
+
+/// The error returned by a [`JoinHandle`] when the task did not complete.
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Cancelled,
+    Panic(Box<dyn Any + Send + 'static>),
+}
+
+impl<T> JoinHandle<T> {
+    /// Wraps the reference to a task whose output is `T`.
+    pub(super) fn new(raw: TaskRef) -> Self {
+        Self {
+            raw,
+            _output: PhantomData,
+        }
+    }
+
+    /// Cancels the task.
+    ///
+    /// The task's future is dropped the next time the executor would poll it
+    /// and awaiting the handle returns a cancelled [`JoinError`]. Aborting a
+    /// task that has already completed has no effect.
+    pub fn abort(&self) {
+        self.raw.abort();
+    }
+
+    /// Returns true if the task has completed, was cancelled or panicked.
+    pub fn is_finished(&self) -> bool {
+        self.raw.is_complete()
+    }
+}
+
+// The output is never pinned.
+impl<T> Unpin for JoinHandle<T> {}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `T` is the output of the task.
+        unsafe { self.raw.poll_output(cx) }
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // SAFETY: this is the task's handle, and it is dropped only once.
+        unsafe { self.raw.drop_join_handle() }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle").finish()
+    }
+}
+
+impl JoinError {
+    pub(crate) fn cancelled() -> Self {
+        Self {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    pub(crate) fn panic(payload: Box<dyn Any + Send + 'static>) -> Self {
+        Self {
+            repr: Repr::Panic(payload),
+        }
+    }
+
+    /// Returns true if the task was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// Returns true if the task panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// Consumes the error, returning the payload the task panicked with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the error does not represent a panic.
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        self.try_into_panic()
+            .expect("`JoinError` reason is not a panic.")
+    }
+
+    /// Consumes the error, returning the panic payload if the task panicked
+    /// and the error itself otherwise.
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send + 'static>, JoinError> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload),
+            _ => Err(self),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "task was cancelled"),
+            Repr::Panic(payload) => match panic_message(payload.as_ref()) {
+                Some(msg) => write!(f, "task panicked with message {:?}", msg),
+                None => write!(f, "task panicked"),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(f, "JoinError::Cancelled"),
+            Repr::Panic(_) => write!(f, "JoinError::Panic({})", self),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+impl From<JoinError> for io::Error {
+    fn from(err: JoinError) -> Self {
+        io::Error::other(err.to_string())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        Some(s)
+    } else {
+        payload.downcast_ref::<String>().map(String::as_str)
+    }
+}
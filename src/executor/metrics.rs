@@ -0,0 +1,172 @@
+use std::cell::Cell;
+use std::fmt;
+use std::time::Duration;
+
+/// Counters kept by the executor for [`RuntimeMetrics`].
+#[derive(Default)]
+pub(crate) struct ExecutorMetrics {
+    pub(crate) spawned: Cell<u64>,
+    pub(crate) completed: Cell<u64>,
+    pub(crate) polls: Cell<u64>,
+}
+
+impl ExecutorMetrics {
+    pub(crate) fn incr(counter: &Cell<u64>) {
+        counter.set(counter.get() + 1);
+    }
+}
+
+/// Counters kept by the reactor for [`RuntimeMetrics`].
+#[derive(Clone, Default)]
+pub(crate) struct ReactorMetrics {
+    pub(crate) waits: u64,
+    pub(crate) wait_time: Duration,
+    pub(crate) events: u64,
+}
+
+/// Number of buckets of a [`Histogram`].
+const BUCKETS: usize = 7;
+
+/// A histogram of task poll durations.
+///
+/// Bucket `i` counts polls that took less than `10^i` microseconds and at
+/// least the upper bound of the previous bucket. The last bucket has no upper
+/// bound.
+#[derive(Clone, Default)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+}
+
+impl Histogram {
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        let mut bucket = 0;
+        while bucket < BUCKETS - 1 && elapsed >= Self::upper_bound(bucket) {
+            bucket += 1;
+        }
+        self.counts[bucket] += 1;
+    }
+
+    fn upper_bound(bucket: usize) -> Duration {
+        Duration::from_micros(10u64.pow(bucket as u32))
+    }
+
+    /// Returns the upper bound and count of every bucket. The upper bound of
+    /// the last bucket is `None`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(i, &count)| {
+            let bound = if i < BUCKETS - 1 {
+                Some(Self::upper_bound(i))
+            } else {
+                None
+            };
+            (bound, count)
+        })
+    }
+}
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (bound, count) in self.buckets() {
+            match bound {
+                Some(bound) => map.entry(&format_args!("<{:?}", bound), &count),
+                None => map.entry(&format_args!("inf"), &count),
+            };
+        }
+        map.finish()
+    }
+}
+
+/// A snapshot of the executor's counters, returned by
+/// [`Handle::metrics`](super::Handle::metrics).
+#[derive(Clone, Debug)]
+pub struct RuntimeMetrics {
+    pub(crate) spawned_tasks: u64,
+    pub(crate) completed_tasks: u64,
+    pub(crate) live_tasks: usize,
+    pub(crate) task_polls: u64,
+    pub(crate) queue_depth: usize,
+    pub(crate) queue_high_water_mark: usize,
+    pub(crate) reactor_waits: u64,
+    pub(crate) reactor_wait_time: Duration,
+    pub(crate) reactor_events: u64,
+    pub(crate) registered_fds: usize,
+    pub(crate) poll_time_histogram: Option<Histogram>,
+}
+
+impl RuntimeMetrics {
+    /// Returns the number of tasks spawned so far.
+    pub fn spawned_tasks(&self) -> u64 {
+        self.spawned_tasks
+    }
+
+    /// Returns the number of tasks that have completed, including tasks that
+    /// panicked or were aborted.
+    pub fn completed_tasks(&self) -> u64 {
+        self.completed_tasks
+    }
+
+    /// Returns the number of tasks that have been spawned but not completed.
+    pub fn live_tasks(&self) -> usize {
+        self.live_tasks
+    }
+
+    /// Returns the number of times a task has been polled.
+    pub fn task_polls(&self) -> u64 {
+        self.task_polls
+    }
+
+    /// Returns the average number of polls per spawned task.
+    pub fn polls_per_task(&self) -> f64 {
+        if self.spawned_tasks == 0 {
+            return 0.0;
+        }
+        self.task_polls as f64 / self.spawned_tasks as f64
+    }
+
+    /// Returns the number of tasks currently in the run queue.
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// Returns the largest number of tasks the run queue has held.
+    pub fn queue_high_water_mark(&self) -> usize {
+        self.queue_high_water_mark
+    }
+
+    /// Returns the number of times the reactor has polled for IO events.
+    pub fn reactor_waits(&self) -> u64 {
+        self.reactor_waits
+    }
+
+    /// Returns the total time spent polling for IO events, including time
+    /// blocked waiting for them.
+    pub fn reactor_wait_time(&self) -> Duration {
+        self.reactor_wait_time
+    }
+
+    /// Returns the number of IO events received by the reactor.
+    pub fn reactor_events(&self) -> u64 {
+        self.reactor_events
+    }
+
+    /// Returns the average number of IO events received per reactor wait.
+    pub fn events_per_wait(&self) -> f64 {
+        if self.reactor_waits == 0 {
+            return 0.0;
+        }
+        self.reactor_events as f64 / self.reactor_waits as f64
+    }
+
+    /// Returns the number of fds registered with the reactor by IO
+    /// resources, not counting the reactor's own.
+    pub fn registered_fds(&self) -> usize {
+        self.registered_fds
+    }
+
+    /// Returns the histogram of task poll durations, if it was enabled with
+    /// [`Builder::enable_poll_time_histogram`](super::Builder::enable_poll_time_histogram).
+    pub fn poll_time_histogram(&self) -> Option<&Histogram> {
+        self.poll_time_histogram.as_ref()
+    }
+}
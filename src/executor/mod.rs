@@ -0,0 +1,1022 @@
+use crate::task::TaskInfo;
+use crate::trace::trace;
+use futures::task::{self, ArcWake, Waker};
+use metrics::{ExecutorMetrics, ReactorMetrics};
+use raw::{Meta, Schedule, TaskRef};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::task::Context;
+use std::time::{Duration, Instant};
+use timer::Timers;
+use wakeup::Wakeup;
+
+mod backend;
+#[cfg(feature = "multi-thread")]
+mod blocking;
+mod builder;
+pub(crate) mod coop;
+mod dump;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod epoll;
+pub(crate) mod handle;
+mod join;
+mod metrics;
+#[cfg(feature = "multi-thread")]
+pub mod multi_thread;
+mod permit;
+mod raw;
+pub(crate) mod registration;
+mod scope;
+mod timer;
+mod wakeup;
+mod yield_now;
+pub use backend::Backend;
+#[cfg(target_os = "wasi")]
+pub use backend::WasmEdgeBackend;
+#[cfg(feature = "multi-thread")]
+pub use blocking::spawn_blocking;
+pub use builder::{Builder, PanicPolicy};
+pub use dump::{Dump, TaskDump, TaskState, WaitingOn};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use epoll::EpollBackend;
+pub use handle::{EnterGuard, Handle};
+pub use join::{JoinError, JoinHandle};
+pub use metrics::{Histogram, RuntimeMetrics};
+pub use permit::{spawn_with_permit, try_spawn};
+pub use scope::{scope, Scope};
+pub(crate) use timer::TimerKey;
+pub use yield_now::yield_now;
+
+pub(crate) const DEFAULT_TASK_QUEUE_SIZE: usize = 4096;
+pub(crate) const DEFAULT_TASKS_PER_TURN: usize = 61;
+
+/// Longest wait for IO when the reactor has no [`Wakeup`], so that tasks woken
+/// from other threads still run soon.
+const MAX_UNINTERRUPTIBLE_WAIT: Duration = Duration::from_millis(10);
+
+/// The directions of an fd that a task waits on, or that are ready.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interest {
+    Read,
+    Write,
+    All,
+}
+
+/// Scheduling priority of a task, chosen when it is spawned with
+/// [`task::Builder::priority`](crate::task::Builder::priority).
+///
+/// Queued tasks of a higher priority run first, and tasks of the same priority
+/// run in the order they were woken. A lower priority level is still served
+/// after being passed over 8 times in a row, so its tasks are delayed but never
+/// starved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    const LEVELS: usize = 3;
+
+    fn level(self) -> usize {
+        self as usize
+    }
+}
+
+/// How many tasks of higher priority may run while a lower priority level
+/// has queued tasks, before that level is served.
+const MAX_SKIPS: u32 = 8;
+
+/// How many tasks in a row may run from the LIFO slot before the queue is
+/// served in FIFO order again.
+const MAX_LIFO_POLLS: u32 = 3;
+
+/// The run queue of an executor, with one FIFO queue per [`Priority`].
+pub struct TaskQueue {
+    inner: Mutex<QueueInner>,
+    /// Interrupts the reactor when a task is queued while it waits.
+    wakeup: Option<Arc<Wakeup>>,
+}
+
+struct QueueInner {
+    /// The task most recently woken by another task, which runs next.
+    lifo: Option<TaskRef>,
+    /// How many tasks in a row were taken from the LIFO slot.
+    lifo_polls: u32,
+    levels: [VecDeque<TaskRef>; Priority::LEVELS],
+    /// How often each level was passed over while it had queued tasks.
+    skipped: [u32; Priority::LEVELS],
+    len: usize,
+    high_water_mark: usize,
+}
+
+#[cfg(feature = "sim")]
+impl QueueInner {
+    /// Removes the `i`-th queued task, counting from the LIFO slot.
+    fn take(&mut self, mut i: usize) -> Option<TaskRef> {
+        if self.lifo.is_some() {
+            if i == 0 {
+                self.len -= 1;
+                return self.lifo.take();
+            }
+            i -= 1;
+        }
+        for level in &mut self.levels {
+            if i < level.len() {
+                self.len -= 1;
+                return level.remove(i);
+            }
+            i -= level.len();
+        }
+        None
+    }
+}
+
+impl TaskQueue {
+    pub fn new() -> Self {
+        Self::new_with_capacity(DEFAULT_TASK_QUEUE_SIZE)
+    }
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(QueueInner {
+                lifo: None,
+                lifo_polls: 0,
+                levels: [
+                    VecDeque::new(),
+                    VecDeque::with_capacity(capacity),
+                    VecDeque::new(),
+                ],
+                skipped: [0; Priority::LEVELS],
+                len: 0,
+                high_water_mark: 0,
+            }),
+            wakeup: None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueInner> {
+        // The lock is never held while user code runs, so it cannot be
+        // poisoned by a panicking task.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn push(&self, runnable: TaskRef) {
+        let mut inner = self.lock();
+        inner.levels[runnable.meta().priority.level()].push_back(runnable);
+        self.pushed(inner);
+    }
+
+    /// Queues a woken task. A task woken by another task running on this
+    /// thread goes to the LIFO slot, where it runs next while the data it was
+    /// woken for is likely still in cache.
+    fn push_woken(&self, runnable: TaskRef) {
+        let current = crate::task::current_id();
+        if current.is_none() || current == Some(runnable.id()) {
+            return self.push(runnable);
+        }
+        let mut inner = self.lock();
+        if let Some(prev) = inner.lifo.replace(runnable) {
+            inner.levels[prev.meta().priority.level()].push_back(prev);
+        }
+        self.pushed(inner);
+    }
+
+    fn pushed(&self, mut inner: MutexGuard<'_, QueueInner>) {
+        inner.len += 1;
+        if inner.len > inner.high_water_mark {
+            inner.high_water_mark = inner.len;
+        }
+        drop(inner);
+        if let Some(wakeup) = &self.wakeup {
+            wakeup.notify();
+        }
+    }
+
+    pub(crate) fn pop(&self) -> Option<TaskRef> {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        // A simulation explores orders of its tasks picked by its seed.
+        #[cfg(feature = "sim")]
+        if let Some(i) = crate::sim::choose(inner.len) {
+            return inner.take(i);
+        }
+        if let Some(task) = inner.lifo.take() {
+            let level = task.meta().priority.level();
+            let preempted = inner.levels[..level].iter().any(|l| !l.is_empty());
+            if inner.lifo_polls < MAX_LIFO_POLLS && !preempted {
+                inner.lifo_polls += 1;
+                inner.len -= 1;
+                return Some(task);
+            }
+            // Tasks waking each other must not starve the rest of the queue.
+            inner.levels[level].push_back(task);
+        }
+        inner.lifo_polls = 0;
+        // The lowest starved level goes first, then the highest non-empty one.
+        let level = (0..Priority::LEVELS)
+            .rev()
+            .find(|&l| inner.skipped[l] >= MAX_SKIPS && !inner.levels[l].is_empty())
+            .or_else(|| (0..Priority::LEVELS).find(|&l| !inner.levels[l].is_empty()))?;
+        for l in 0..Priority::LEVELS {
+            if l == level || inner.levels[l].is_empty() {
+                inner.skipped[l] = 0;
+            } else {
+                inner.skipped[l] += 1;
+            }
+        }
+        inner.len -= 1;
+        inner.levels[level].pop_front()
+    }
+
+    /// Returns true if no task is queued.
+    pub fn is_empty(&self) -> bool {
+        self.lock().len == 0
+    }
+
+    /// Returns the number of tasks in the queue.
+    pub fn len(&self) -> usize {
+        self.lock().len
+    }
+
+    /// Returns the largest number of tasks the queue has held.
+    pub fn high_water_mark(&self) -> usize {
+        self.lock().high_water_mark
+    }
+
+    fn queued_ids(&self) -> HashSet<TaskId> {
+        let inner = self.lock();
+        let queued = inner.levels.iter().flatten().chain(&inner.lifo);
+        queued.map(TaskRef::id).collect()
+    }
+
+    fn clear(&self) {
+        let mut inner = self.lock();
+        inner.lifo = None;
+        inner.levels.iter_mut().for_each(VecDeque::clear);
+        inner.len = 0;
+    }
+}
+
+impl Default for TaskQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schedule for TaskQueue {
+    fn schedule(&self, task: TaskRef) {
+        self.push_woken(task);
+    }
+}
+
+/// Identifies a task spawned on an executor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(usize);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A waker registered with the reactor, and the task it belongs to.
+struct Parked {
+    waker: Waker,
+    task: Option<TaskId>,
+}
+
+impl Parked {
+    fn new(cx: &Context) -> Self {
+        Self {
+            waker: cx.waker().clone(),
+            task: crate::task::current_id(),
+        }
+    }
+}
+
+pub struct Reactor {
+    backend: Arc<dyn Backend>,
+    /// The fds registered with the backend.
+    fds: HashSet<RawFd>,
+    wakers_map: HashMap<u64, Parked>,
+    pub(crate) timers: Timers,
+    /// Ready fds not dispatched yet.
+    events: VecDeque<(RawFd, Interest)>,
+    max_events: usize,
+    metrics: ReactorMetrics,
+    wakeup: Option<Arc<Wakeup>>,
+}
+
+impl Reactor {
+    pub fn new() -> Self {
+        Self::with_max_events(usize::MAX)
+    }
+
+    /// Creates a reactor that dispatches at most `max_events` events per turn.
+    ///
+    /// Events beyond the limit are kept and dispatched by the following turns
+    /// before the poller is consulted again.
+    pub fn with_max_events(max_events: usize) -> Self {
+        Self::with_backend(backend::default_backend(), max_events)
+    }
+
+    /// Creates a reactor waiting with `backend`, see
+    /// [`Reactor::with_max_events`].
+    pub fn with_backend(backend: Arc<dyn Backend>, max_events: usize) -> Self {
+        // Without a wakeup, waits cannot be interrupted and are kept short
+        // instead.
+        let wakeup = if backend.needs_wakeup() {
+            Wakeup::new().ok().map(Arc::new)
+        } else {
+            None
+        };
+        let mut reactor = Self {
+            backend,
+            fds: HashSet::new(),
+            wakers_map: HashMap::new(),
+            timers: Timers::default(),
+            events: VecDeque::new(),
+            max_events: max_events.max(1),
+            metrics: ReactorMetrics::default(),
+            wakeup,
+        };
+        if let Some(fd) = reactor.wakeup.as_ref().map(|wakeup| wakeup.fd()) {
+            if reactor.add(fd).is_err() {
+                reactor.wakeup = None;
+            }
+        }
+        reactor
+    }
+
+    /// Returns the wakeup interrupting waits of this reactor, if it could be
+    /// created.
+    pub(crate) fn wakeup(&self) -> Option<Arc<Wakeup>> {
+        self.wakeup.clone()
+    }
+
+    /// Blocks until at least one registered fd is ready and wakes the tasks
+    /// waiting on it.
+    pub fn wait(&mut self) -> std::io::Result<()> {
+        self.turn(None)
+    }
+
+    /// Like [`Reactor::wait`], but returns after `timeout` even if no fd is
+    /// ready. A zero timeout only collects the events that are already
+    /// pending.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+        self.turn(Some(timeout))
+    }
+
+    fn turn(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        if self.events.is_empty() {
+            let timeout = self.clamp_timeout(timeout);
+            let start = Instant::now();
+            let events = self.backend.poll(&self.interests(), timeout);
+            self.record_wait(timeout, start.elapsed(), events)?;
+        }
+        for waker in self.dispatch() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Like [`Reactor::turn`], but releases the lock while waiting so that
+    /// other threads can register interest or drop IO resources in the
+    /// meantime. Registrations made during the wait take effect with the next
+    /// turn.
+    fn turn_shared(reactor: &Mutex<Reactor>, timeout: Option<Duration>) -> std::io::Result<()> {
+        let mut guard = Reactor::lock(reactor);
+        if guard.events.is_empty() {
+            let timeout = guard.clamp_timeout(timeout);
+            let (backend, interests) = (guard.backend.clone(), guard.interests());
+            drop(guard);
+            let start = Instant::now();
+            let events = backend.poll(&interests, timeout);
+            guard = Reactor::lock(reactor);
+            guard.record_wait(timeout, start.elapsed(), events)?;
+        }
+        let wakers = guard.dispatch();
+        drop(guard);
+        for waker in wakers {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn lock(reactor: &Mutex<Reactor>) -> MutexGuard<'_, Reactor> {
+        // Wakers are woken after the lock is released, so no user code runs
+        // while it is held.
+        reactor.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns what to wait for next: the directions of each fd that a task
+    /// waits on. A connected socket is almost always writable, and would end
+    /// every wait right away if it were waited on regardless.
+    fn interests(&self) -> Vec<(RawFd, Interest)> {
+        let wakeup = self.wakeup.as_ref().map(|wakeup| wakeup.fd());
+        let interest = |fd: RawFd| {
+            let token = fd as u64 * 2;
+            let read = Some(fd) == wakeup || self.wakers_map.contains_key(&token);
+            let write = self.wakers_map.contains_key(&(token + 1));
+            match (read, write) {
+                (true, true) => Some((fd, Interest::All)),
+                (true, false) => Some((fd, Interest::Read)),
+                (false, true) => Some((fd, Interest::Write)),
+                (false, false) => None,
+            }
+        };
+        self.fds.iter().filter_map(|&fd| interest(fd)).collect()
+    }
+
+    /// Shortens `timeout` so that the wait does not sleep past the next timer.
+    fn clamp_timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        match self.timers.next_deadline() {
+            Some(deadline) => {
+                let until = deadline.saturating_duration_since(crate::time::now());
+                Some(timeout.map_or(until, |timeout| timeout.min(until)))
+            }
+            None => timeout,
+        }
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn record_wait(
+        &mut self,
+        timeout: Option<Duration>,
+        elapsed: Duration,
+        events: std::io::Result<Vec<(RawFd, Interest)>>,
+    ) -> std::io::Result<()> {
+        self.metrics.waits += 1;
+        self.metrics.wait_time += elapsed;
+        let events = events?;
+        trace!(?timeout, ?elapsed, events = events.len(), "reactor wait");
+        self.metrics.events += events.len() as u64;
+        self.events.extend(events);
+        Ok(())
+    }
+
+    /// Takes the wakers of up to `max_events` buffered events and of the
+    /// expired timers.
+    fn dispatch(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        let n = self.max_events.min(self.events.len());
+        for (fd, interest) in self.events.drain(..n).collect::<Vec<_>>() {
+            if let Some(wakeup) = self.wakeup.as_ref().filter(|w| w.fd() == fd) {
+                wakeup.drain();
+                continue;
+            }
+            // A missing waker means nobody is interested in the event anymore,
+            // e.g. the fd was deleted before a buffered event was dispatched.
+            let token = fd as u64 * 2;
+            if interest != Interest::Write {
+                wakers.extend(self.wakers_map.remove(&token).map(|p| p.waker));
+            }
+            if interest != Interest::Read {
+                wakers.extend(self.wakers_map.remove(&(token + 1)).map(|p| p.waker));
+            }
+        }
+        self.timers.expire(crate::time::now(), &mut wakers);
+        wakers
+    }
+
+    /// Returns what each task parked in the reactor is waiting on.
+    fn parked_tasks(&self) -> HashMap<TaskId, Vec<WaitingOn>> {
+        let mut parked: HashMap<TaskId, Vec<WaitingOn>> = HashMap::new();
+        for (token, p) in &self.wakers_map {
+            if let Some(task) = p.task {
+                let fd = (token / 2) as RawFd;
+                let waiting_on = if token % 2 == 0 {
+                    WaitingOn::Read(fd)
+                } else {
+                    WaitingOn::Write(fd)
+                };
+                parked.entry(task).or_default().push(waiting_on);
+            }
+        }
+        for (task, deadline) in self.timers.parked() {
+            parked
+                .entry(task)
+                .or_default()
+                .push(WaitingOn::Timer(deadline));
+        }
+        parked
+    }
+    pub fn add(&mut self, fd: RawFd) -> std::io::Result<()> {
+        trace!(fd, "reactor add");
+        self.backend.register(fd)?;
+        self.fds.insert(fd);
+        Ok(())
+    }
+
+    /// Returns the number of fds registered with the reactor, not counting
+    /// the one it uses internally to interrupt waits.
+    pub fn registered_fds(&self) -> usize {
+        self.fds.len() - usize::from(self.wakeup.is_some())
+    }
+
+    pub fn delete(&mut self, fd: RawFd) {
+        trace!(fd, "reactor delete");
+        self.wakers_map.remove(&(fd as u64 * 2));
+        self.wakers_map.remove(&(fd as u64 * 2 + 1));
+        if self.fds.remove(&fd) {
+            self.backend.deregister(fd);
+        }
+    }
+
+    pub fn modify(&mut self, fd: RawFd, interest: Interest, cx: &mut Context) {
+        trace!(fd, ?interest, "reactor modify");
+        match interest {
+            Interest::Read => {
+                self.wakers_map.insert(fd as u64 * 2, Parked::new(cx));
+            }
+            Interest::Write => {
+                self.wakers_map.insert(fd as u64 * 2 + 1, Parked::new(cx));
+            }
+            Interest::All => {
+                self.wakers_map.insert(fd as u64 * 2, Parked::new(cx));
+                self.wakers_map.insert(fd as u64 * 2 + 1, Parked::new(cx));
+            }
+        }
+    }
+}
+
+impl Default for Reactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State shared between an [`Executor`] and all of its [`Handle`]s.
+pub(crate) struct Shared {
+    tasks: Arc<TaskQueue>,
+    /// Every task that has not completed yet.
+    owned: RefCell<HashMap<TaskId, TaskRef>>,
+    /// Waiting for the number of live tasks to drop below
+    /// [`Builder::max_tasks`].
+    capacity_waiters: RefCell<Vec<Waker>>,
+    next_task_id: Cell<usize>,
+    pub(crate) reactor: Arc<Mutex<Reactor>>,
+    config: builder::Config,
+    metrics: ExecutorMetrics,
+    poll_time_histogram: Option<RefCell<Histogram>>,
+}
+
+impl Shared {
+    pub(crate) fn spawn_local<F>(
+        &self,
+        future: F,
+        name: Option<&str>,
+        priority: Priority,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+    {
+        #[cfg(feature = "sim")]
+        let future = crate::sim::OnHost::current(future);
+        let id = TaskId(self.next_task_id.get());
+        self.next_task_id.set(id.0 + 1);
+        let info = TaskInfo::new(id, name);
+        ExecutorMetrics::incr(&self.metrics.spawned);
+        if let Some(f) = &self.config.on_task_spawn {
+            f(&info);
+        }
+        let meta = Meta {
+            #[cfg(feature = "tracing")]
+            span: tracing::trace_span!("task", id = %id, name = info.name()),
+            info,
+            priority,
+        };
+        let queue = Arc::downgrade(&self.tasks) as Weak<dyn Schedule>;
+        let (task, join_handle) =
+            TaskRef::new(future, Some(queue), self.config.panic_policy, Some(meta));
+        self.owned.borrow_mut().insert(id, task.clone());
+        task.schedule();
+        join_handle
+    }
+
+    fn run_task(&self, task: TaskRef) {
+        // A stale wakeup of a task that completed in the meantime.
+        if task.is_complete() {
+            return;
+        }
+        let meta = task.meta();
+        let waker = task.waker_ref();
+        let mut context = Context::from_waker(&waker);
+        let timed = self.poll_time_histogram.is_some() || self.config.slow_poll.is_some();
+        let start = timed.then(Instant::now);
+        #[cfg(feature = "tracing")]
+        let _span = meta.span.clone().entered();
+        // Also releases the task if its panic is resumed below, under
+        // `PanicPolicy::AbortRuntime`, so that a reused executor does not
+        // count it as live.
+        let _release = ReleaseOnComplete {
+            shared: self,
+            task: &task,
+        };
+        let poll = crate::task::enter(&meta.info, || {
+            coop::with_budget(self.config.task_budget, || {
+                // SAFETY: tasks are only polled here, on the executor's thread.
+                unsafe { task.poll(&mut context) }
+            })
+        });
+        ExecutorMetrics::incr(&self.metrics.polls);
+        if let Some(elapsed) = start.map(|start| start.elapsed()) {
+            if let Some(histogram) = &self.poll_time_histogram {
+                histogram.borrow_mut().record(elapsed);
+            }
+            if let Some((threshold, f)) = &self.config.slow_poll {
+                if elapsed >= *threshold {
+                    f(&meta.info, elapsed);
+                }
+            }
+        }
+        if poll.is_ready() {
+            trace!("task completed");
+        }
+    }
+
+    /// Forgets a completed task, releasing its slot under
+    /// [`Builder::max_tasks`].
+    fn release(&self, task: &TaskRef) {
+        self.owned.borrow_mut().remove(&task.id());
+        self.notify_capacity();
+        ExecutorMetrics::incr(&self.metrics.completed);
+        if let Some(f) = &self.config.on_task_terminate {
+            f(&task.meta().info);
+        }
+    }
+
+    /// Drops the futures of all remaining tasks.
+    fn cancel_all(&self) {
+        let owned = std::mem::take(&mut *self.owned.borrow_mut());
+        for task in owned.values() {
+            // SAFETY: `Shared` is not `Send`, so this runs on the executor's
+            // thread, and no task is being polled.
+            unsafe { task.cancel() };
+            if let Some(f) = &self.config.on_task_terminate {
+                f(&task.meta().info);
+            }
+        }
+        drop(owned);
+        self.tasks.clear();
+        self.notify_capacity();
+    }
+
+    /// Returns true if another task may be spawned under
+    /// [`Builder::max_tasks`].
+    fn has_capacity(&self) -> bool {
+        let max = self.config.max_tasks.unwrap_or(usize::MAX);
+        self.owned.borrow().len() < max
+    }
+
+    /// Wakes the tasks waiting in [`spawn_with_permit`]. All of them are woken,
+    /// so that a waiter that gave up cannot keep the others waiting.
+    fn notify_capacity(&self) {
+        let waiters = std::mem::take(&mut *self.capacity_waiters.borrow_mut());
+        waiters.into_iter().for_each(Waker::wake);
+    }
+
+    fn metrics(&self) -> RuntimeMetrics {
+        let reactor = Reactor::lock(&self.reactor);
+        RuntimeMetrics {
+            spawned_tasks: self.metrics.spawned.get(),
+            completed_tasks: self.metrics.completed.get(),
+            live_tasks: self.owned.borrow().len(),
+            task_polls: self.metrics.polls.get(),
+            queue_depth: self.tasks.len(),
+            queue_high_water_mark: self.tasks.high_water_mark(),
+            reactor_waits: reactor.metrics.waits,
+            reactor_wait_time: reactor.metrics.wait_time,
+            reactor_events: reactor.metrics.events,
+            registered_fds: reactor.registered_fds(),
+            poll_time_histogram: self
+                .poll_time_histogram
+                .as_ref()
+                .map(|histogram| histogram.borrow().clone()),
+        }
+    }
+
+    fn dump(&self) -> Dump {
+        let queued = self.tasks.queued_ids();
+        let mut parked = Reactor::lock(&self.reactor).parked_tasks();
+        let mut tasks = Vec::new();
+        let running = crate::task::current_id();
+        for (id, task) in self.owned.borrow().iter() {
+            let state = if running == Some(*id) {
+                TaskState::Running
+            } else if queued.contains(id) {
+                TaskState::Queued
+            } else if let Some(waiting_on) = parked.remove(id) {
+                TaskState::Parked(waiting_on)
+            } else {
+                TaskState::Idle
+            };
+            tasks.push(TaskDump::new(task.meta().info.clone(), state));
+        }
+        tasks.sort_by_key(|task| task.info().id());
+        Dump::new(tasks)
+    }
+
+    /// Polls up to [`Builder::tasks_per_turn`] queued tasks.
+    fn run_tasks(&self) {
+        for _ in 0..self.config.tasks_per_turn {
+            match self.tasks.pop() {
+                Some(t) => self.run_task(t),
+                None => break,
+            }
+        }
+    }
+
+    /// Waits for IO events for at most `timeout`, or indefinitely if it is
+    /// `None`.
+    ///
+    /// The wait is skipped if a task is queued or `root` was woken, and
+    /// interrupted if that happens while waiting.
+    fn park(&self, timeout: Option<Duration>, root: Option<&RootWaker>) -> std::io::Result<()> {
+        if timeout == Some(Duration::ZERO) {
+            return Reactor::turn_shared(&self.reactor, timeout);
+        }
+        // Tasks queued from now on interrupt the wait.
+        let _parked = self.tasks.wakeup.as_ref().map(|wakeup| wakeup.park());
+        if !self.tasks.is_empty() || root.is_some_and(|root| root.woken.load(Ordering::SeqCst)) {
+            return Reactor::turn_shared(&self.reactor, Some(Duration::ZERO));
+        }
+        let interruptible =
+            self.tasks.wakeup.is_some() || !Reactor::lock(&self.reactor).backend.needs_wakeup();
+        let timeout = if interruptible {
+            timeout
+        } else {
+            Some(timeout.map_or(MAX_UNINTERRUPTIBLE_WAIT, |timeout| {
+                timeout.min(MAX_UNINTERRUPTIBLE_WAIT)
+            }))
+        };
+        if let Some(f) = &self.config.before_park {
+            f();
+        }
+        let ret = Reactor::turn_shared(&self.reactor, timeout);
+        if let Some(f) = &self.config.after_unpark {
+            f();
+        }
+        ret
+    }
+}
+
+/// Releases a task from its [`Shared`] on drop if the task has completed.
+struct ReleaseOnComplete<'a> {
+    shared: &'a Shared,
+    task: &'a TaskRef,
+}
+
+impl Drop for ReleaseOnComplete<'_> {
+    fn drop(&mut self) {
+        if self.task.is_complete() {
+            self.shared.release(self.task);
+        }
+    }
+}
+
+/// A single-threaded executor driving spawned tasks and the reactor.
+///
+/// An executor can be run any number of times, with [`Executor::block_on`],
+/// [`Executor::poll_once`] or [`Executor::run_until_stalled`]. Tasks spawned
+/// during one run that have not completed stay alive and continue in the next
+/// one, and so do their reactor registrations and timers. Wakeups happening
+/// in between are queued and processed by the next run.
+///
+/// Dropping the executor cancels all remaining tasks.
+pub struct Executor {
+    handle: Handle,
+}
+
+/// Waker of the future passed to [`Executor::block_on`].
+struct RootWaker {
+    woken: AtomicBool,
+    wakeup: Option<Arc<Wakeup>>,
+}
+
+impl ArcWake for RootWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::SeqCst);
+        if let Some(wakeup) = &arc_self.wakeup {
+            wakeup.notify();
+        }
+    }
+}
+
+thread_local! {
+    /// Whether an executor is running on this thread.
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks an executor as running on the current thread until dropped.
+struct RunGuard;
+
+impl RunGuard {
+    fn enter() -> std::io::Result<Self> {
+        if RUNNING.with(|running| running.replace(true)) {
+            return Err(std::io::Error::other(
+                "cannot run an executor from within a running executor, \
+                 e.g. by calling `block_on` inside a task; \
+                 use `spawn` or `.await` instead",
+            ));
+        }
+        Ok(RunGuard)
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(false));
+    }
+}
+
+/// Spawns a future onto the current executor.
+///
+/// The returned [`JoinHandle`] resolves to the output of the future, or to a
+/// [`JoinError`] if the task panicked.
+///
+/// # Panics
+///
+/// Panics if called outside of [`Executor::block_on`] or [`Handle::enter`].
+/// With the `multi-thread` feature, the future is spawned onto the
+/// multi-threaded runtime instead when called from one of its tasks.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "multi-thread")]
+    if let Some(handle) = multi_thread_handle() {
+        return handle.spawn(future);
+    }
+    spawn_local(future)
+}
+
+/// Returns the multi-threaded runtime that `spawn` and its variants use, i.e.
+/// the one entered on this thread unless an executor is entered as well.
+#[cfg(feature = "multi-thread")]
+pub(crate) fn multi_thread_handle() -> Option<multi_thread::Handle> {
+    match Handle::try_current() {
+        Ok(_) => None,
+        Err(_) => multi_thread::Handle::try_current().ok(),
+    }
+}
+
+/// Spawns a future that is not `Send` onto the current executor.
+///
+/// The executor runs every task on the thread that called `block_on`, so the
+/// future may hold `Rc`, `RefCell` and other thread-bound state.
+///
+/// # Panics
+///
+/// Panics if called outside of [`Executor::block_on`] or [`Handle::enter`].
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    handle::with_current(|handle| handle.shared.spawn_local(future, None, Priority::Normal))
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Builder::new().build()
+    }
+
+    /// Returns a [`Builder`] to configure a new executor.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Returns a handle to this executor.
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Runs the future returned by `f` to completion, driving spawned tasks
+    /// and the reactor in the meantime.
+    ///
+    /// The root future is polled on the calling thread only and therefore
+    /// does not need to be `Send`.
+    ///
+    /// At most [`Builder::tasks_per_turn`] tasks are polled between two polls
+    /// of the reactor. The reactor only blocks when neither the root future
+    /// nor any task is runnable, so IO events are processed with a bounded
+    /// delay even under a busy workload.
+    ///
+    /// Returns as soon as the root future completes; spawned tasks that are
+    /// still alive are resumed by the next run of the executor.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an executor is already running on the current thread, i.e.
+    /// when called from a task or from the root future of another
+    /// `block_on`. The same applies to [`Executor::poll_once`] and
+    /// [`Executor::run_until_stalled`].
+    pub fn block_on<F, T, O>(&mut self, f: F) -> std::io::Result<O>
+    where
+        F: Fn() -> T,
+        T: Future<Output = O> + 'static,
+    {
+        let root = Arc::new(RootWaker {
+            woken: AtomicBool::new(true),
+            wakeup: self.handle.shared.tasks.wakeup.clone(),
+        });
+        let waker = task::waker(root.clone());
+        let mut cx = Context::from_waker(&waker);
+        let _running = RunGuard::enter()?;
+        let _guard = self.handle.enter();
+        let shared = &self.handle.shared;
+        let mut fut = f();
+        let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+        let ret = loop {
+            if root.woken.swap(false, Ordering::SeqCst) {
+                let poll =
+                    coop::with_budget(shared.config.task_budget, || fut.as_mut().poll(&mut cx));
+                if let std::task::Poll::Ready(t) = poll {
+                    break t;
+                }
+            }
+            shared.run_tasks();
+
+            // Only block when there is nothing left to run.
+            if root.woken.load(Ordering::SeqCst) || !shared.tasks.is_empty() {
+                shared.park(Some(Duration::ZERO), None)?;
+            } else {
+                shared.park(None, Some(&root))?;
+            }
+        };
+        Ok(ret)
+    }
+
+    /// Runs one turn of the executor, for hosts that drive the event loop
+    /// themselves instead of calling [`Executor::block_on`].
+    ///
+    /// Polls up to [`Builder::tasks_per_turn`] queued tasks and then polls
+    /// the reactor once. The reactor waits for at most `timeout`, and does not
+    /// wait at all if tasks are still queued.
+    ///
+    /// Returns true if tasks are runnable, i.e. `poll_once` should be called
+    /// again right away.
+    pub fn poll_once(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        let _running = RunGuard::enter()?;
+        let _guard = self.handle.enter();
+        let shared = &self.handle.shared;
+        shared.run_tasks();
+        if shared.tasks.is_empty() {
+            shared.park(Some(timeout), None)?;
+        } else {
+            shared.park(Some(Duration::ZERO), None)?;
+        }
+        Ok(!shared.tasks.is_empty())
+    }
+
+    /// Runs tasks until none of them can make progress without waiting, for
+    /// hosts that drive the event loop themselves.
+    ///
+    /// The reactor is only polled without blocking. Returns true if tasks are
+    /// still alive, waiting for IO, timers or each other.
+    pub fn run_until_stalled(&mut self) -> std::io::Result<bool> {
+        let _running = RunGuard::enter()?;
+        let _guard = self.handle.enter();
+        let shared = &self.handle.shared;
+        loop {
+            shared.run_tasks();
+            shared.park(Some(Duration::ZERO), None)?;
+            if shared.tasks.is_empty() {
+                break;
+            }
+        }
+        Ok(!shared.owned.borrow().is_empty())
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        // Drop the remaining tasks within the executor's context, so that
+        // the IO resources they own deregister from the reactor.
+        let _guard = self.handle.enter();
+        self.handle.shared.cancel_all();
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // Tasks spawned through a `Handle` after the executor was dropped.
+        self.cancel_all();
+    }
+}
@@ -0,0 +1,291 @@
+//! A multi-threaded runtime, enabled with the `multi-thread` feature.
+//!
+//! The runtime runs a pool of worker threads, e.g. on `wasm32-wasip1-threads`
+//! with wasi-threads or on a native target. Every worker has its own run
+//! queue and steals from the others when it runs out of tasks. All workers
+//! share one reactor, which is polled by whichever worker is idle.
+//!
+//! Tasks have to be `Send`. Inside a task, [`spawn`](crate::spawn),
+//! [`task::Builder::spawn`](crate::task::Builder::spawn),
+//! [`try_spawn`](crate::try_spawn), [`spawn_with_permit`](crate::spawn_with_permit),
+//! [`JoinSet::spawn`](crate::task::JoinSet::spawn), [`sleep`](crate::time::sleep)
+//! and the TCP types use the runtime the task runs on. Task names,
+//! [`task::current`](crate::task::current) and the `tracing` spans of tasks
+//! work as on the single-threaded [`Executor`](super::Executor).
+//!
+//! The following is only supported by the single-threaded executor:
+//!
+//! - Non-`Send` tasks, i.e. [`spawn_local`](crate::spawn_local) and its
+//!   variants, which panic when called from a task of this runtime.
+//! - [`Handle::current`](super::Handle::current), and with it
+//!   [`Handle::metrics`](super::Handle::metrics) and
+//!   [`Handle::dump`](super::Handle::dump).
+//! - Task priorities, which this runtime ignores, and the limit of
+//!   [`Builder::max_tasks`](super::Builder::max_tasks), so `try_spawn` always
+//!   succeeds here.
+//! - The task hooks and the slow poll detector of the executor
+//!   [`Builder`](super::Builder).
+
+use super::backend::{self, Backend};
+use super::{JoinHandle, PanicPolicy, Reactor, RunGuard, DEFAULT_TASKS_PER_TURN};
+use futures::task::{self, ArcWake};
+use std::future::Future;
+use std::io;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Context;
+use std::thread::{self, Thread};
+use worker::{Config, Shared};
+
+mod worker;
+
+/// Builds a [`Runtime`] with custom configuration.
+pub struct Builder {
+    worker_threads: usize,
+    thread_name: String,
+    max_events_per_turn: usize,
+    backend: Option<Arc<dyn Backend>>,
+    config: Config,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            worker_threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            thread_name: "wasmedge-async-worker".to_string(),
+            max_events_per_turn: usize::MAX,
+            backend: None,
+            config: Config {
+                tasks_per_turn: DEFAULT_TASKS_PER_TURN,
+                task_budget: super::coop::DEFAULT_BUDGET,
+                panic_policy: PanicPolicy::IsolateTask,
+            },
+        }
+    }
+
+    /// Sets the number of worker threads, which defaults to the available
+    /// parallelism.
+    pub fn worker_threads(mut self, n: usize) -> Self {
+        self.worker_threads = n.max(1);
+        self
+    }
+
+    /// Sets the name of the worker threads.
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    /// Sets the maximum number of IO events dispatched per reactor turn.
+    pub fn max_events_per_turn(mut self, n: usize) -> Self {
+        self.max_events_per_turn = n;
+        self
+    }
+
+    /// Sets the backend the reactor waits for IO with.
+    pub fn backend(mut self, backend: impl Backend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Sets how many tasks a worker polls before it collects IO events.
+    pub fn tasks_per_turn(mut self, n: usize) -> Self {
+        self.config.tasks_per_turn = n.max(1);
+        self
+    }
+
+    /// Sets the budget of operations a task may perform per poll.
+    pub fn task_budget(mut self, budget: usize) -> Self {
+        self.config.task_budget = budget.max(1);
+        self
+    }
+
+    /// Sets what happens when a task panics.
+    ///
+    /// With [`PanicPolicy::AbortRuntime`] the runtime shuts down and the
+    /// panic is resumed by [`Runtime::block_on`].
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.config.panic_policy = policy;
+        self
+    }
+
+    /// Starts the worker threads.
+    pub fn build(self) -> io::Result<Runtime> {
+        let backend = self.backend.unwrap_or_else(backend::default_backend);
+        let shared = Arc::new(Shared::new(
+            self.worker_threads,
+            Reactor::with_backend(backend, self.max_events_per_turn),
+            self.config,
+        ));
+        let mut runtime = Runtime {
+            handle: Handle {
+                shared: shared.clone(),
+            },
+            threads: Vec::with_capacity(self.worker_threads),
+        };
+        for index in 0..self.worker_threads {
+            let shared = shared.clone();
+            let thread = thread::Builder::new()
+                .name(format!("{}-{}", self.thread_name, index))
+                .spawn(move || worker::run(shared, index))?;
+            runtime.threads.push(thread);
+        }
+        Ok(runtime)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A runtime polling `Send` tasks on a pool of worker threads.
+///
+/// Dropping the runtime stops the workers and cancels the remaining tasks.
+pub struct Runtime {
+    handle: Handle,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Runtime {
+    /// Creates a runtime with one worker per available core.
+    pub fn new() -> io::Result<Self> {
+        Builder::new().build()
+    }
+
+    /// Returns a [`Builder`] to configure a new runtime.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Returns a handle to this runtime.
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Spawns a future onto the worker threads.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+
+    /// Runs the future returned by `f` to completion on the calling thread,
+    /// while the workers run spawned tasks.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the reactor fails, or if an executor is already running on
+    /// the current thread, e.g. when called from a task.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of a task under [`PanicPolicy::AbortRuntime`].
+    pub fn block_on<F, T, O>(&self, f: F) -> io::Result<O>
+    where
+        F: FnOnce() -> T,
+        T: Future<Output = O>,
+    {
+        let _running = RunGuard::enter()?;
+        let shared = &self.handle.shared;
+        let _guard = worker::enter(shared, None);
+        let root = Arc::new(ThreadWaker {
+            thread: thread::current(),
+            woken: AtomicBool::new(true),
+        });
+        let waker = task::waker(root.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = f();
+        let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+        loop {
+            if let Some(e) = shared.take_error() {
+                return Err(e);
+            }
+            if root.woken.swap(false, Ordering::SeqCst) {
+                if let std::task::Poll::Ready(t) = fut.as_mut().poll(&mut cx) {
+                    return Ok(t);
+                }
+            }
+            // Wake up now and then to notice a failed runtime.
+            thread::park_timeout(worker::MAX_WAIT);
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        let shared = &self.handle.shared;
+        shared.shutdown();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        // Drop the remaining tasks within the runtime's context, so that the
+        // IO resources they own deregister from the reactor.
+        let _guard = worker::enter(shared, None);
+        shared.cancel_all();
+    }
+}
+
+/// Waker of the future passed to [`Runtime::block_on`].
+struct ThreadWaker {
+    thread: Thread,
+    woken: AtomicBool,
+}
+
+impl ArcWake for ThreadWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::SeqCst);
+        arc_self.thread.unpark();
+    }
+}
+
+/// A cloneable reference to a [`Runtime`], which can be sent to other
+/// threads.
+#[derive(Clone)]
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl Handle {
+    /// Returns a handle to the runtime entered on this thread, i.e. when
+    /// called from one of its tasks or from [`Runtime::block_on`].
+    pub fn try_current() -> io::Result<Self> {
+        worker::current()
+            .map(|shared| Handle { shared })
+            .ok_or_else(|| {
+                io::Error::other("there is no multi-threaded runtime running on this thread")
+            })
+    }
+
+    /// Spawns a future onto the worker threads.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_named(future, None)
+    }
+
+    /// Spawns a future named `name` onto the worker threads.
+    pub(crate) fn spawn_named<F>(&self, future: F, name: Option<&str>) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future, name)
+    }
+
+    pub(crate) fn panic_policy(&self) -> PanicPolicy {
+        self.shared.config.panic_policy
+    }
+}
+
+/// Returns the reactor of the runtime entered on this thread, if any.
+pub(crate) fn current_reactor() -> Option<Arc<Mutex<Reactor>>> {
+    worker::current().map(|shared| shared.reactor.clone())
+}
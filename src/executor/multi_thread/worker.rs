@@ -0,0 +1,314 @@
+use super::super::raw::{Meta, Schedule, TaskRef};
+use super::super::wakeup::Wakeup;
+use super::super::{coop, JoinHandle, PanicPolicy, Priority, Reactor, RunGuard, TaskId};
+use crate::task::TaskInfo;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::task::Context;
+use std::time::Duration;
+
+/// Longest time a worker waits for IO or sleeps before checking for work
+/// again.
+///
+/// Queued tasks interrupt the wait, but interest registered while another
+/// worker waits for IO is only picked up by the next wait, so this bounds the
+/// delay of such registrations.
+pub(super) const MAX_WAIT: Duration = Duration::from_millis(10);
+
+thread_local! {
+    /// The runtime entered on this thread, and the index of the worker if
+    /// this thread is one.
+    static CONTEXT: RefCell<Option<(Arc<Shared>, Option<usize>)>> = const { RefCell::new(None) };
+}
+
+pub(super) struct Config {
+    pub(super) tasks_per_turn: usize,
+    pub(super) task_budget: usize,
+    pub(super) panic_policy: PanicPolicy,
+}
+
+/// State shared by the workers of a runtime and its handles.
+pub(super) struct Shared {
+    /// Tasks scheduled from outside of the workers.
+    injector: Mutex<VecDeque<TaskRef>>,
+    /// The run queue of each worker.
+    queues: Vec<Mutex<VecDeque<TaskRef>>>,
+    pub(super) reactor: Arc<Mutex<Reactor>>,
+    /// Held by the worker that waits for IO.
+    driver: Mutex<()>,
+    /// Interrupts the wait of the driver when a task is queued.
+    wakeup: Option<Arc<Wakeup>>,
+    /// Number of workers waiting on `condvar`.
+    sleepers: Mutex<usize>,
+    condvar: Condvar,
+    /// Every task that has not completed yet.
+    tasks: Mutex<HashMap<TaskId, TaskRef>>,
+    next_task_id: AtomicUsize,
+    shutdown: AtomicBool,
+    /// The first reactor error, which stops the runtime.
+    error: Mutex<Option<io::Error>>,
+    /// The panic of a task under `PanicPolicy::AbortRuntime`.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    pub(super) config: Config,
+}
+
+pub(super) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // No lock is held while user code runs, so a poisoned lock still guards
+    // consistent data.
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Shared {
+    pub(super) fn new(workers: usize, reactor: Reactor, config: Config) -> Self {
+        Self {
+            injector: Mutex::new(VecDeque::new()),
+            queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            wakeup: reactor.wakeup(),
+            reactor: Arc::new(Mutex::new(reactor)),
+            driver: Mutex::new(()),
+            sleepers: Mutex::new(0),
+            condvar: Condvar::new(),
+            tasks: Mutex::new(HashMap::new()),
+            next_task_id: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            error: Mutex::new(None),
+            panic: Mutex::new(None),
+            config,
+        }
+    }
+
+    pub(super) fn spawn<F>(self: &Arc<Self>, future: F, name: Option<&str>) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let id = TaskId(self.next_task_id.fetch_add(1, Ordering::Relaxed));
+        let info = TaskInfo::new(id, name);
+        let meta = Meta {
+            #[cfg(feature = "tracing")]
+            span: tracing::trace_span!("task", id = %id, name = info.name()),
+            info,
+            priority: Priority::Normal,
+        };
+        let scheduler = Arc::downgrade(self) as Weak<dyn Schedule>;
+        let (task, join_handle) = TaskRef::new(
+            future,
+            Some(scheduler),
+            self.config.panic_policy,
+            Some(meta),
+        );
+        lock(&self.tasks).insert(id, task.clone());
+        task.schedule();
+        join_handle
+    }
+
+    /// Polls `task`, which the worker took from a run queue.
+    fn run_task(&self, task: TaskRef) {
+        let meta = task.meta();
+        let waker = task.waker_ref();
+        let mut cx = Context::from_waker(&waker);
+        #[cfg(feature = "tracing")]
+        let _span = meta.span.clone().entered();
+        let poll = crate::task::enter(&meta.info, || {
+            coop::with_budget(self.config.task_budget, || {
+                // SAFETY: the task was queued on this runtime, and its future
+                // is `Send`.
+                unsafe { task.poll(&mut cx) }
+            })
+        });
+        if poll.is_ready() {
+            lock(&self.tasks).remove(&task.id());
+        }
+    }
+
+    fn next_task(&self, index: usize) -> Option<TaskRef> {
+        if let Some(task) = lock(&self.queues[index]).pop_front() {
+            return Some(task);
+        }
+        if let Some(task) = lock(&self.injector).pop_front() {
+            return Some(task);
+        }
+        self.steal(index)
+    }
+
+    /// Moves half of the tasks of another worker's queue to the queue of
+    /// worker `index`, and returns one of them.
+    fn steal(&self, index: usize) -> Option<TaskRef> {
+        let n = self.queues.len();
+        for i in 1..n {
+            let mut victim = lock(&self.queues[(index + i) % n]);
+            let len = victim.len();
+            if len == 0 {
+                continue;
+            }
+            let mut stolen = victim.split_off(len / 2);
+            drop(victim);
+            let task = stolen.pop_front();
+            lock(&self.queues[index]).extend(stolen);
+            return task;
+        }
+        None
+    }
+
+    fn has_work(&self) -> bool {
+        !lock(&self.injector).is_empty() || self.queues.iter().any(|q| !lock(q).is_empty())
+    }
+
+    /// Waits for IO unless another worker already does. Returns false if the
+    /// reactor was busy.
+    fn drive(&self, timeout: Duration) -> bool {
+        let _driver = match self.driver.try_lock() {
+            Ok(driver) => driver,
+            Err(_) => return false,
+        };
+        let _parked = self
+            .wakeup
+            .as_ref()
+            .filter(|_| !timeout.is_zero())
+            .map(|wakeup| wakeup.park());
+        let timeout = if self.has_work() {
+            Duration::ZERO
+        } else {
+            timeout
+        };
+        if let Err(e) = Reactor::turn_shared(&self.reactor, Some(timeout)) {
+            self.fail(e);
+        }
+        true
+    }
+
+    fn sleep(&self) {
+        let mut sleepers = lock(&self.sleepers);
+        if self.has_work() || self.is_shutdown() {
+            return;
+        }
+        *sleepers += 1;
+        // The timeout lets an idle worker take over waiting for IO when the
+        // previous driver went back to running tasks.
+        let (mut sleepers, _) = self
+            .condvar
+            .wait_timeout(sleepers, MAX_WAIT)
+            .unwrap_or_else(|e| e.into_inner());
+        *sleepers -= 1;
+    }
+
+    fn fail(&self, e: io::Error) {
+        lock(&self.error).get_or_insert(e);
+        self.shutdown();
+    }
+
+    pub(super) fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Release);
+        let _sleepers = lock(&self.sleepers);
+        self.condvar.notify_all();
+        if let Some(wakeup) = &self.wakeup {
+            wakeup.notify();
+        }
+    }
+
+    pub(super) fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    /// Returns the error that stopped the runtime, resuming the panic of a
+    /// task if that is what stopped it.
+    pub(super) fn take_error(&self) -> Option<io::Error> {
+        if let Some(payload) = lock(&self.panic).take() {
+            panic::resume_unwind(payload);
+        }
+        lock(&self.error).take()
+    }
+
+    /// Drops the futures of all remaining tasks.
+    pub(super) fn cancel_all(&self) {
+        let tasks = std::mem::take(&mut *lock(&self.tasks));
+        for task in tasks.into_values() {
+            // SAFETY: the workers have stopped, so no task is being polled.
+            unsafe { task.cancel() };
+        }
+        lock(&self.injector).clear();
+        for queue in &self.queues {
+            lock(queue).clear();
+        }
+    }
+}
+
+impl Schedule for Shared {
+    /// Queues `task` on the current worker, or on the injection queue when
+    /// called from another thread.
+    fn schedule(&self, task: TaskRef) {
+        let worker = CONTEXT.with(|ctx| match ctx.borrow().as_ref() {
+            Some((shared, worker)) if std::ptr::eq(Arc::as_ptr(shared), self) => *worker,
+            _ => None,
+        });
+        match worker {
+            Some(index) => lock(&self.queues[index]).push_back(task),
+            None => lock(&self.injector).push_back(task),
+        }
+        if *lock(&self.sleepers) > 0 {
+            self.condvar.notify_one();
+        }
+        if let Some(wakeup) = &self.wakeup {
+            wakeup.notify();
+        }
+    }
+}
+
+/// Makes `shared` the current runtime of this thread until the guard is
+/// dropped.
+pub(super) fn enter(shared: &Arc<Shared>, worker: Option<usize>) -> EnterGuard {
+    let prev = CONTEXT.with(|ctx| ctx.borrow_mut().replace((shared.clone(), worker)));
+    EnterGuard { prev }
+}
+
+pub(super) struct EnterGuard {
+    prev: Option<(Arc<Shared>, Option<usize>)>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = prev);
+    }
+}
+
+/// Returns the runtime entered on this thread.
+pub(super) fn current() -> Option<Arc<Shared>> {
+    CONTEXT.with(|ctx| ctx.borrow().as_ref().map(|(shared, _)| shared.clone()))
+}
+
+/// Runs worker `index` until the runtime shuts down.
+pub(super) fn run(shared: Arc<Shared>, index: usize) {
+    let _running = RunGuard::enter().expect("worker threads run no other executor");
+    let _guard = enter(&shared, Some(index));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut polled = 0;
+        while !shared.is_shutdown() {
+            match shared.next_task(index) {
+                Some(task) => {
+                    shared.run_task(task);
+                    polled += 1;
+                    // Collect IO events regularly even when busy.
+                    if polled % shared.config.tasks_per_turn == 0 {
+                        shared.drive(Duration::ZERO);
+                    }
+                }
+                None => {
+                    if !shared.drive(MAX_WAIT) {
+                        shared.sleep();
+                    }
+                }
+            }
+        }
+    }));
+    if let Err(payload) = result {
+        lock(&shared.panic).get_or_insert(payload);
+        shared.shutdown();
+    }
+}
@@ -0,0 +1,82 @@
+use super::{handle, JoinHandle, Priority};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Spawns a future onto the current executor, unless it already runs
+/// [`Builder::max_tasks`](super::Builder::max_tasks) live tasks.
+///
+/// Fails with [`io::ErrorKind::WouldBlock`] when the limit is reached, e.g. so
+/// that a server can reject a connection instead of exhausting memory.
+///
+/// With the `multi-thread` feature, the future is spawned onto the
+/// multi-threaded runtime instead when called from one of its tasks. That
+/// runtime has no task limit, so this always succeeds there.
+///
+/// # Panics
+///
+/// Panics if called outside of [`Executor::block_on`](super::Executor::block_on)
+/// or [`Handle::enter`](super::Handle::enter).
+pub fn try_spawn<F>(future: F) -> io::Result<JoinHandle<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "multi-thread")]
+    if let Some(handle) = super::multi_thread_handle() {
+        return Ok(handle.spawn(future));
+    }
+    handle::with_current(|handle| {
+        if !handle.shared.has_capacity() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the executor runs the maximum number of tasks",
+            ));
+        }
+        Ok(handle.shared.spawn_local(future, None, Priority::Normal))
+    })
+}
+
+/// Spawns a future onto the current executor once it runs fewer than
+/// [`Builder::max_tasks`](super::Builder::max_tasks) live tasks.
+///
+/// Waits for other tasks to complete while the limit is reached, applying
+/// backpressure to the caller, e.g. an accept loop. Like [`try_spawn`], this
+/// spawns onto the multi-threaded runtime without waiting when called from
+/// one of its tasks.
+///
+/// # Panics
+///
+/// Panics if polled outside of [`Executor::block_on`](super::Executor::block_on).
+pub async fn spawn_with_permit<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "multi-thread")]
+    if let Some(handle) = super::multi_thread_handle() {
+        return handle.spawn(future);
+    }
+    WaitCapacity.await;
+    handle::with_current(|handle| handle.shared.spawn_local(future, None, Priority::Normal))
+}
+
+struct WaitCapacity;
+
+impl Future for WaitCapacity {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        handle::with_current(|handle| {
+            if handle.shared.has_capacity() {
+                return Poll::Ready(());
+            }
+            let mut waiters = handle.shared.capacity_waiters.borrow_mut();
+            if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+}
@@ -0,0 +1,617 @@
+//! The raw representation of a spawned task.
+//!
+//! A task is a single allocation holding a [`Header`] followed by the future,
+//! which is replaced by its output once it completes. The header carries a
+//! state word, combining a reference count with the scheduling, cancellation
+//! and join flags, and a vtable to reach the future and output without
+//! knowing their types. [`TaskRef`] is a counted reference to a task, and
+//! wakers are `TaskRef`s behind a [`RawWakerVTable`], so creating, cloning and
+//! waking a waker never allocates.
+
+use super::{JoinError, JoinHandle, PanicPolicy, Priority, TaskId};
+use crate::task::TaskInfo;
+use futures::task::AtomicWaker;
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::mem::{self, ManuallyDrop};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Weak;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The task is in a run queue.
+const SCHEDULED: usize = 1 << 0;
+/// The task is being polled.
+const RUNNING: usize = 1 << 1;
+/// The task was woken while it was polled, and is queued again afterwards.
+const NOTIFIED: usize = 1 << 2;
+/// The future has completed or was cancelled, and has been dropped.
+const COMPLETE: usize = 1 << 3;
+/// The task was aborted, and is cancelled the next time it is polled.
+const CANCELLED: usize = 1 << 4;
+/// The `JoinHandle` of the task is alive, and takes the output.
+const JOIN_INTEREST: usize = 1 << 5;
+/// One reference in the state word; the bits below it are flags.
+const REF_ONE: usize = 1 << 6;
+
+/// The payload of a panic.
+type Panic = Box<dyn Any + Send>;
+
+/// Queues the tasks that are woken, e.g. the run queue of an executor.
+pub(crate) trait Schedule: Send + Sync {
+    fn schedule(&self, task: TaskRef);
+}
+
+/// What an executor records about a task it spawned.
+pub(crate) struct Meta {
+    pub(crate) info: TaskInfo,
+    pub(crate) priority: Priority,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: tracing::Span,
+}
+
+pub(crate) struct Header {
+    state: AtomicUsize,
+    vtable: &'static Vtable,
+    /// Where the task is queued when woken. Tasks without one are polled by
+    /// whoever created them.
+    scheduler: Option<Weak<dyn Schedule>>,
+    policy: PanicPolicy,
+    /// Woken once the task completes.
+    join_waker: AtomicWaker,
+    meta: Option<Meta>,
+}
+
+struct Vtable {
+    /// Polls the future and stores its output once it completes. Returns the
+    /// payload of a panic that is to be resumed under
+    /// [`PanicPolicy::AbortRuntime`].
+    poll: unsafe fn(NonNull<Header>, &mut Context<'_>) -> Poll<Option<Panic>>,
+    /// Drops the future and stores a cancelled `JoinError` as output.
+    cancel: unsafe fn(NonNull<Header>),
+    /// Moves the output to the `Poll<Result<T, JoinError>>` pointed to.
+    read_output: unsafe fn(NonNull<Header>, *mut ()),
+    drop_output: unsafe fn(NonNull<Header>),
+    dealloc: unsafe fn(NonNull<Header>),
+}
+
+enum Stage<F: Future> {
+    Running(F),
+    Finished(Result<F::Output, JoinError>),
+    Consumed,
+}
+
+#[repr(C)]
+struct Cell<F: Future> {
+    header: Header,
+    stage: UnsafeCell<Stage<F>>,
+}
+
+/// A counted reference to a task.
+///
+/// Only the header is accessed through a `TaskRef` in general. The future is
+/// only polled and dropped by whoever the task is queued on, and the
+/// `RUNNING` flag keeps two threads from polling it at once; tasks that are
+/// not `Send` are only queued on the thread that spawned them. The output is
+/// taken by the `JoinHandle` once the task has completed, or dropped by the
+/// task if there is no handle. The last reference may therefore be released
+/// anywhere, as only memory is left to free by then.
+pub(crate) struct TaskRef {
+    ptr: NonNull<Header>,
+}
+
+// SAFETY: see above, the future is never touched through a `TaskRef` that
+// was sent to another thread unless it is `Send`.
+unsafe impl Send for TaskRef {}
+unsafe impl Sync for TaskRef {}
+
+impl TaskRef {
+    /// Allocates a task running `future`, which is queued on `scheduler`
+    /// when woken. The returned reference is the one owned by the caller.
+    pub(crate) fn new<F>(
+        future: F,
+        scheduler: Option<Weak<dyn Schedule>>,
+        policy: PanicPolicy,
+        meta: Option<Meta>,
+    ) -> (Self, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+    {
+        // SAFETY: the future lives as long as needed.
+        unsafe { Self::new_unchecked(future, scheduler, policy, meta) }
+    }
+
+    /// Like [`TaskRef::new`], for a future borrowing data.
+    ///
+    /// # Safety
+    ///
+    /// The future must be dropped, by completing or by [`TaskRef::cancel`],
+    /// before the data it borrows goes away. If it never is, it is leaked.
+    pub(crate) unsafe fn new_unchecked<'a, F>(
+        future: F,
+        scheduler: Option<Weak<dyn Schedule>>,
+        policy: PanicPolicy,
+        meta: Option<Meta>,
+    ) -> (Self, JoinHandle<F::Output>)
+    where
+        F: Future + 'a,
+    {
+        let cell = Box::new(Cell {
+            header: Header {
+                state: AtomicUsize::new((2 * REF_ONE) | JOIN_INTEREST),
+                vtable: vtable::<F>(),
+                scheduler,
+                policy,
+                join_waker: AtomicWaker::new(),
+                meta,
+            },
+            stage: UnsafeCell::new(Stage::Running(future)),
+        });
+        let task = TaskRef {
+            ptr: NonNull::from(Box::leak(cell)).cast(),
+        };
+        let join_handle = JoinHandle::new(TaskRef { ptr: task.ptr });
+        (task, join_handle)
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the task is alive as long as a reference to it exists.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns what the executor recorded about the task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task was not spawned on an executor, e.g. a child of a
+    /// [`scope`](super::scope).
+    pub(crate) fn meta(&self) -> &Meta {
+        self.header()
+            .meta
+            .as_ref()
+            .expect("task was not spawned on an executor")
+    }
+
+    pub(crate) fn id(&self) -> TaskId {
+        self.meta().info.id()
+    }
+
+    /// Queues the task unless it is queued already or has completed. A task
+    /// woken while it is polled is queued once the poll returns.
+    pub(crate) fn schedule(&self) {
+        let prev = self.update(|state| {
+            if state & (SCHEDULED | NOTIFIED | COMPLETE) != 0 {
+                None
+            } else if state & RUNNING != 0 {
+                Some(state | NOTIFIED)
+            } else {
+                Some(state | SCHEDULED)
+            }
+        });
+        if matches!(prev, Ok(prev) if prev & RUNNING == 0) {
+            self.push();
+        }
+    }
+
+    fn push(&self) {
+        // Wakeups after the scheduler is dropped are ignored.
+        let scheduler = self.header().scheduler.as_ref().and_then(Weak::upgrade);
+        if let Some(scheduler) = scheduler {
+            scheduler.schedule(self.clone());
+        }
+    }
+
+    /// Polls the future, or cancels it if the task was aborted, and stores
+    /// the output once it completes.
+    ///
+    /// The task is unscheduled before the poll, so that it is queued again if
+    /// it is woken while running. Returns `Ready` right away if the task has
+    /// already completed.
+    ///
+    /// # Panics
+    ///
+    /// Resumes a panic of the future under [`PanicPolicy::AbortRuntime`],
+    /// once the task is complete.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by whoever the task is queued on, and on the
+    /// thread that spawned it unless the future is `Send`.
+    pub(crate) unsafe fn poll(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let header = self.header();
+        let prev = match self
+            .update(|state| (state & COMPLETE == 0).then_some(state & !SCHEDULED | RUNNING))
+        {
+            Ok(prev) => prev,
+            Err(_) => return Poll::Ready(()),
+        };
+        if prev & CANCELLED != 0 {
+            (header.vtable.cancel)(self.ptr);
+            self.complete();
+            return Poll::Ready(());
+        }
+        if let Poll::Ready(panic) = (header.vtable.poll)(self.ptr, cx) {
+            self.complete();
+            if let Some(payload) = panic {
+                panic::resume_unwind(payload);
+            }
+            return Poll::Ready(());
+        }
+        let prev = self.update(|state| {
+            Some(if state & NOTIFIED != 0 {
+                state & !(RUNNING | NOTIFIED) | SCHEDULED
+            } else {
+                state & !RUNNING
+            })
+        });
+        if matches!(prev, Ok(prev) if prev & NOTIFIED != 0) {
+            self.push();
+        }
+        Poll::Pending
+    }
+
+    /// Drops the future of a task that has not completed, and completes it
+    /// with a cancelled `JoinError`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`TaskRef::poll`].
+    pub(crate) unsafe fn cancel(&self) {
+        if self
+            .update(|state| (state & COMPLETE == 0).then_some(state | RUNNING))
+            .is_ok()
+        {
+            (self.header().vtable.cancel)(self.ptr);
+            self.complete();
+        }
+    }
+
+    /// Marks the task complete once its output is stored, and hands the
+    /// output to the `JoinHandle`, or drops it if the handle is gone.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be polling the task.
+    unsafe fn complete(&self) {
+        let prev = self
+            .header()
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                Some(state & !(RUNNING | NOTIFIED) | COMPLETE)
+            });
+        if prev.unwrap() & JOIN_INTEREST == 0 {
+            (self.header().vtable.drop_output)(self.ptr);
+        } else {
+            self.header().join_waker.wake();
+        }
+    }
+
+    /// Returns true if the future has completed or was cancelled.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.header().state.load(Ordering::Acquire) & COMPLETE != 0
+    }
+
+    /// Cancels the task the next time it is polled, and queues it so that
+    /// this happens soon. Has no effect once the task has completed.
+    pub(crate) fn abort(&self) {
+        let prev = self.header().state.fetch_or(CANCELLED, Ordering::AcqRel);
+        if prev & CANCELLED == 0 {
+            self.schedule();
+        }
+    }
+
+    /// Takes the output of the task once it has completed, and registers
+    /// `cx` to be woken then otherwise.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called through the task's `JoinHandle`, and `T` must be
+    /// the output type of the future.
+    pub(crate) unsafe fn poll_output<T>(&self, cx: &mut Context<'_>) -> Poll<Result<T, JoinError>> {
+        let header = self.header();
+        if !self.is_complete() {
+            header.join_waker.register(cx.waker());
+            // The task may have completed before the waker was registered.
+            if !self.is_complete() {
+                return Poll::Pending;
+            }
+        }
+        let mut output = Poll::Pending;
+        (header.vtable.read_output)(self.ptr, &mut output as *mut _ as *mut ());
+        output
+    }
+
+    /// Gives up the interest of the `JoinHandle` in the output, dropping the
+    /// output if the task has completed already.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called once, when the task's `JoinHandle` is dropped.
+    pub(crate) unsafe fn drop_join_handle(&self) {
+        let header = self.header();
+        let prev = header.state.fetch_and(!JOIN_INTEREST, Ordering::AcqRel);
+        if prev & COMPLETE != 0 {
+            (header.vtable.drop_output)(self.ptr);
+        }
+        header.join_waker.take();
+    }
+
+    /// Returns a waker for the task without touching the reference count.
+    ///
+    /// The waker must not outlive `self`; cloning it takes a reference.
+    pub(crate) fn waker_ref(&self) -> ManuallyDrop<Waker> {
+        let raw = RawWaker::new(self.ptr.as_ptr() as *const (), &WAKER_VTABLE);
+        // SAFETY: the vtable upholds the `RawWaker` contract, and dropping is
+        // prevented by `ManuallyDrop`.
+        ManuallyDrop::new(unsafe { Waker::from_raw(raw) })
+    }
+
+    /// Applies `f` to the state word until it sticks, returning the previous
+    /// state, or the current one if `f` returned `None`.
+    fn update(&self, f: impl FnMut(usize) -> Option<usize>) -> Result<usize, usize> {
+        self.header()
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, f)
+    }
+
+    fn into_raw(self) -> *const () {
+        ManuallyDrop::new(self).ptr.as_ptr() as *const ()
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must come from [`TaskRef::into_raw`], or be borrowed from a live
+    /// `TaskRef` and not be dropped.
+    unsafe fn from_raw(ptr: *const ()) -> Self {
+        TaskRef {
+            ptr: NonNull::new_unchecked(ptr as *mut Header),
+        }
+    }
+}
+
+impl Clone for TaskRef {
+    fn clone(&self) -> Self {
+        self.header().state.fetch_add(REF_ONE, Ordering::Relaxed);
+        TaskRef { ptr: self.ptr }
+    }
+}
+
+impl Drop for TaskRef {
+    fn drop(&mut self) {
+        let prev = self.header().state.fetch_sub(REF_ONE, Ordering::AcqRel);
+        if prev & !(REF_ONE - 1) == REF_ONE {
+            // SAFETY: this was the last reference.
+            unsafe { (self.header().vtable.dealloc)(self.ptr) }
+        }
+    }
+}
+
+fn vtable<F: Future>() -> &'static Vtable {
+    &Vtable {
+        poll: poll::<F>,
+        cancel: cancel::<F>,
+        read_output: read_output::<F>,
+        drop_output: drop_output::<F>,
+        dealloc: dealloc::<F>,
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must point to a live `Cell<F>`, and the caller must have exclusive
+/// access to its stage.
+unsafe fn stage<'a, F: Future>(ptr: NonNull<Header>) -> &'a mut Stage<F> {
+    &mut *ptr.cast::<Cell<F>>().as_ref().stage.get()
+}
+
+/// Replaces the stage, dropping the previous one afterwards so that a panic
+/// while dropping it cannot leave it in place.
+unsafe fn set_stage<F: Future>(ptr: NonNull<Header>, next: Stage<F>) {
+    drop(mem::replace(stage::<F>(ptr), next));
+}
+
+unsafe fn poll<F: Future>(ptr: NonNull<Header>, cx: &mut Context<'_>) -> Poll<Option<Panic>> {
+    let future = match stage::<F>(ptr) {
+        // SAFETY: the future is never moved out of its allocation.
+        Stage::Running(future) => Pin::new_unchecked(future),
+        _ => unreachable!("polled a task that has completed"),
+    };
+    let output = match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+        Ok(Poll::Pending) => return Poll::Pending,
+        Ok(Poll::Ready(output)) => Ok(output),
+        Err(payload) => match ptr.as_ref().policy {
+            PanicPolicy::IsolateTask => Err(JoinError::panic(payload)),
+            PanicPolicy::AbortRuntime => {
+                set_stage::<F>(ptr, Stage::Finished(Err(JoinError::cancelled())));
+                return Poll::Ready(Some(payload));
+            }
+        },
+    };
+    set_stage::<F>(ptr, Stage::Finished(output));
+    Poll::Ready(None)
+}
+
+unsafe fn cancel<F: Future>(ptr: NonNull<Header>) {
+    set_stage::<F>(ptr, Stage::Finished(Err(JoinError::cancelled())));
+}
+
+unsafe fn read_output<F: Future>(ptr: NonNull<Header>, dst: *mut ()) {
+    let stage = stage::<F>(ptr);
+    if !matches!(stage, Stage::Finished(_)) {
+        panic!("`JoinHandle` polled after completion");
+    }
+    if let Stage::Finished(output) = mem::replace(stage, Stage::Consumed) {
+        *(dst as *mut Poll<Result<F::Output, JoinError>>) = Poll::Ready(output);
+    }
+}
+
+unsafe fn drop_output<F: Future>(ptr: NonNull<Header>) {
+    set_stage::<F>(ptr, Stage::Consumed);
+}
+
+unsafe fn dealloc<F: Future>(ptr: NonNull<Header>) {
+    let mut cell = Box::from_raw(ptr.cast::<Cell<F>>().as_ptr());
+    // By now the future and the output have been dropped where they belong,
+    // unless the task was leaked, e.g. with its scope. They must not be
+    // dropped on whichever thread releases the last reference.
+    mem::forget(mem::replace(cell.stage.get_mut(), Stage::Consumed));
+}
+
+static WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_waker, wake, wake_by_ref, drop_waker);
+
+unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
+    let task = ManuallyDrop::new(TaskRef::from_raw(ptr));
+    RawWaker::new((*task).clone().into_raw(), &WAKER_VTABLE)
+}
+
+unsafe fn wake(ptr: *const ()) {
+    TaskRef::from_raw(ptr).schedule();
+}
+
+unsafe fn wake_by_ref(ptr: *const ()) {
+    ManuallyDrop::new(TaskRef::from_raw(ptr)).schedule();
+}
+
+unsafe fn drop_waker(ptr: *const ()) {
+    drop(TaskRef::from_raw(ptr));
+}
+
+// These tests only use a plain queue as the scheduler, so that they also run
+// under miri.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+    use futures::future::{self, FutureExt};
+    use std::collections::VecDeque;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Queue(Mutex<VecDeque<TaskRef>>);
+
+    impl Queue {
+        fn pop(&self) -> Option<TaskRef> {
+            self.0.lock().unwrap().pop_front()
+        }
+    }
+
+    impl Schedule for Queue {
+        fn schedule(&self, task: TaskRef) {
+            self.0.lock().unwrap().push_back(task);
+        }
+    }
+
+    /// Counts how often it is dropped.
+    struct DropCount(Arc<AtomicUsize>);
+
+    impl Drop for DropCount {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn spawn<F>(queue: &Arc<Queue>, future: F) -> (TaskRef, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+    {
+        let queue = Arc::downgrade(queue) as Weak<dyn Schedule>;
+        TaskRef::new(future, Some(queue), PanicPolicy::IsolateTask, None)
+    }
+
+    fn run(task: &TaskRef) -> Poll<()> {
+        let waker = task.waker_ref();
+        unsafe { task.poll(&mut Context::from_waker(&waker)) }
+    }
+
+    #[test]
+    fn wake_after_complete() {
+        let queue = Arc::new(Queue::default());
+        let (tx, rx) = oneshot::channel();
+        let (task, handle) = spawn(&queue, async move { rx.await.unwrap() });
+        let waker = (*task.waker_ref()).clone();
+        task.schedule();
+        assert!(run(&queue.pop().unwrap()).is_pending());
+        tx.send(7).unwrap();
+        assert!(run(&queue.pop().unwrap()).is_ready());
+        assert!(task.is_complete());
+
+        // Stale wakers neither queue the task nor keep it from being freed.
+        waker.wake_by_ref();
+        assert!(queue.pop().is_none());
+        assert_eq!(handle.now_or_never().unwrap().unwrap(), 7);
+        drop(task);
+        waker.wake();
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn drop_while_scheduled() {
+        let queue = Arc::new(Queue::default());
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let guard = DropCount(dropped.clone());
+        let (task, handle) = spawn(&queue, async move {
+            let _guard = guard;
+            future::pending::<()>().await
+        });
+        task.schedule();
+        let waker = (*task.waker_ref()).clone();
+
+        // An executor dropped with the task in its queue cancels the task,
+        // then drops the queue.
+        unsafe { task.cancel() };
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        drop(queue);
+        drop(task);
+        assert!(handle.now_or_never().unwrap().unwrap_err().is_cancelled());
+
+        // The task is freed by its last waker, which has nowhere to queue it.
+        waker.wake();
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn join_handle_dropped_before_completion() {
+        let queue = Arc::new(Queue::default());
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let output = DropCount(dropped.clone());
+        let (task, handle) = spawn(&queue, async move { output });
+        drop(handle);
+        assert!(run(&task).is_ready());
+        // Without a handle, the task drops the output itself.
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        drop(task);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn join_handle_dropped_after_completion() {
+        let queue = Arc::new(Queue::default());
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let output = DropCount(dropped.clone());
+        let (task, handle) = spawn(&queue, async move { output });
+        assert!(run(&task).is_ready());
+        drop(task);
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        // The handle drops the output it did not take, and frees the task as
+        // its last reference.
+        drop(handle);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn abort_cancels_on_next_poll() {
+        let queue = Arc::new(Queue::default());
+        let (task, handle) = spawn(&queue, future::pending::<()>());
+        handle.abort();
+        assert!(!handle.is_finished());
+        assert!(run(&queue.pop().unwrap()).is_ready());
+        assert!(handle.is_finished());
+        assert!(handle.now_or_never().unwrap().unwrap_err().is_cancelled());
+        drop(task);
+    }
+}
@@ -0,0 +1,50 @@
+use super::{handle, Interest, Reactor};
+use std::io;
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::Context;
+
+/// An fd registered with the reactor of the executor it was created in.
+///
+/// The registration keeps pointing to that reactor wherever the owning
+/// resource is moved or dropped, so that it is always deregistered from the
+/// reactor that tracks it.
+pub(crate) struct Registration {
+    reactor: Weak<Mutex<Reactor>>,
+    fd: RawFd,
+}
+
+impl Registration {
+    /// Registers `fd` with the reactor of the current executor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the executor context.
+    pub(crate) fn new(fd: RawFd) -> io::Result<Self> {
+        let reactor = handle::current_reactor();
+        Reactor::lock(&reactor).add(fd)?;
+        Ok(Self {
+            reactor: Arc::downgrade(&reactor),
+            fd,
+        })
+    }
+
+    /// Wakes the task of `cx` once the fd is ready for `interest`.
+    ///
+    /// Fails if the executor the fd was registered with has been dropped.
+    pub(crate) fn set_interest(&self, interest: Interest, cx: &mut Context<'_>) -> io::Result<()> {
+        let reactor = self.reactor.upgrade().ok_or_else(|| {
+            io::Error::other("the executor this IO resource was created in has been dropped")
+        })?;
+        Reactor::lock(&reactor).modify(self.fd, interest, cx);
+        Ok(())
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(reactor) = self.reactor.upgrade() {
+            Reactor::lock(&reactor).delete(self.fd);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use super::builder::PanicPolicy;
+use super::raw::{Schedule, TaskRef};
+use super::{Handle, JoinHandle};
+use futures::task::AtomicWaker;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::task::{Context, Poll};
+
+/// Creates a scope for spawning tasks that borrow from the enclosing stack.
+///
+/// The future returned by `f` is given a [`Scope`] to spawn child tasks with.
+/// Unlike [`spawn`](super::spawn), children only need to live as long as
+/// `'env`. The returned future completes once `f`'s future and every child
+/// have completed, and dropping it cancels all children that are still
+/// running. Children are `Send`, like those of [`spawn`](super::spawn), so
+/// the scope future is `Send` if `f`'s future is, and can be awaited by
+/// tasks of the multi-threaded runtime.
+///
+/// Children are polled by the scope future itself rather than queued on the
+/// executor, which is what keeps the borrows sound even if the scope future
+/// is leaked: its children are then never polled or dropped again.
+///
+/// ```ignore
+/// let mut buffers = vec![vec![0u8; 1024]; 4];
+/// scope(|s| async move {
+///     for (stream, buf) in streams.iter_mut().zip(buffers.iter_mut()) {
+///         s.spawn(async move { stream.read(buf).await });
+///     }
+/// })
+/// .await;
+/// ```
+pub fn scope<'env, F, Fut>(f: F) -> impl Future<Output = Fut::Output> + 'env
+where
+    F: FnOnce(Scope<'env>) -> Fut,
+    Fut: Future + 'env,
+{
+    let policy = match Handle::try_current() {
+        Ok(handle) => handle.shared.config.panic_policy,
+        #[cfg(feature = "multi-thread")]
+        Err(_) => super::multi_thread::Handle::try_current()
+            .map_or(PanicPolicy::IsolateTask, |handle| handle.panic_policy()),
+        #[cfg(not(feature = "multi-thread"))]
+        Err(_) => PanicPolicy::IsolateTask,
+    };
+    let scope = Scope {
+        children: Arc::new(Children::default()),
+        ready: Arc::new(ReadyQueue::default()),
+        policy,
+        _env: PhantomData,
+    };
+    ScopeFuture {
+        body: Box::pin(f(scope.clone())),
+        output: None,
+        scope,
+    }
+}
+
+/// A handle to spawn tasks inside a [`scope`].
+#[derive(Clone)]
+pub struct Scope<'env> {
+    children: Arc<Children>,
+    ready: Arc<ReadyQueue>,
+    policy: PanicPolicy,
+    /// Children may borrow anything that lives for `'env`.
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+/// The children of a scope that have not completed yet.
+#[derive(Default)]
+struct Children(Mutex<Vec<TaskRef>>);
+
+impl Children {
+    fn lock(&self) -> MutexGuard<'_, Vec<TaskRef>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drops the futures of all children.
+    fn cancel_all(&self) {
+        // Taken out first, as dropping a child may spawn another one.
+        let children = std::mem::take(&mut *self.lock());
+        for child in &children {
+            // SAFETY: children are only polled by their scope, which is not
+            // polling them now.
+            unsafe { child.cancel() };
+        }
+    }
+}
+
+impl Drop for Children {
+    fn drop(&mut self) {
+        // Children spawned after the scope completed.
+        self.cancel_all();
+    }
+}
+
+/// The children of a scope that were woken, and the waker of the scope.
+#[derive(Default)]
+struct ReadyQueue {
+    tasks: Mutex<VecDeque<TaskRef>>,
+    waker: AtomicWaker,
+}
+
+impl ReadyQueue {
+    fn pop(&self) -> Option<TaskRef> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+impl Schedule for ReadyQueue {
+    fn schedule(&self, task: TaskRef) {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(task);
+        self.waker.wake();
+    }
+}
+
+impl<'env> Scope<'env> {
+    /// Spawns a task that may borrow data living for `'env`.
+    ///
+    /// The task is guaranteed to complete, or to be cancelled, before the
+    /// enclosing [`scope`] completes.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'env,
+        F::Output: Send + 'env,
+    {
+        let ready = Arc::downgrade(&self.ready) as Weak<dyn Schedule>;
+        // SAFETY: the scope future cancels its children when dropped, and
+        // `Children` cancels those spawned afterwards, both within `'env`. A
+        // leaked scope leaks its children along with their futures.
+        let (task, join_handle) =
+            unsafe { TaskRef::new_unchecked(future, Some(ready), self.policy, None) };
+        self.children.lock().push(task.clone());
+        task.schedule();
+        join_handle
+    }
+}
+
+struct ScopeFuture<'env, Fut: Future> {
+    body: Pin<Box<Fut>>,
+    output: Option<Fut::Output>,
+    scope: Scope<'env>,
+}
+
+impl<Fut: Future> Unpin for ScopeFuture<'_, Fut> {}
+
+impl<Fut: Future> Future for ScopeFuture<'_, Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Fut::Output> {
+        let this = &mut *self;
+        this.scope.ready.waker.register(cx.waker());
+        if this.output.is_none() {
+            if let Poll::Ready(output) = this.body.as_mut().poll(cx) {
+                this.output = Some(output);
+            }
+        }
+        // Children woken while this runs, e.g. spawned by other children,
+        // wake the scope to run in its next poll.
+        let mut completed = false;
+        for _ in 0..this.scope.ready.len() {
+            let Some(child) = this.scope.ready.pop() else {
+                break;
+            };
+            let waker = child.waker_ref();
+            // SAFETY: children are only polled here, by their scope, and their
+            // futures are `Send`.
+            completed |= unsafe { child.poll(&mut Context::from_waker(&waker)) }.is_ready();
+        }
+        let mut children = this.scope.children.lock();
+        if completed {
+            children.retain(|child| !child.is_complete());
+        }
+        if children.is_empty() {
+            if let Some(output) = this.output.take() {
+                return Poll::Ready(output);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<Fut: Future> Drop for ScopeFuture<'_, Fut> {
+    fn drop(&mut self) {
+        self.scope.children.cancel_all();
+    }
+}
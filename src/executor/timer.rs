@@ -0,0 +1,62 @@
+//! The timers of a reactor, behind [`time::sleep`](crate::time::sleep).
+
+use super::{Parked, TaskId};
+use std::collections::BTreeMap;
+use std::task::{Context, Waker};
+use std::time::Instant;
+
+/// Key of a timer registered with the reactor. The sequence number keeps
+/// timers with the same deadline apart.
+pub(crate) type TimerKey = (Instant, u64);
+
+/// The pending timers of a reactor, ordered by deadline.
+#[derive(Default)]
+pub(crate) struct Timers {
+    entries: BTreeMap<TimerKey, Parked>,
+    next: u64,
+}
+
+impl Timers {
+    /// Registers a timer firing at `deadline`, replacing the timer `key` if
+    /// given.
+    pub(crate) fn insert(
+        &mut self,
+        key: Option<TimerKey>,
+        deadline: Instant,
+        cx: &mut Context,
+    ) -> TimerKey {
+        if let Some(key) = key {
+            self.entries.remove(&key);
+        }
+        let key = (deadline, self.next);
+        self.next += 1;
+        self.entries.insert(key, Parked::new(cx));
+        key
+    }
+
+    pub(crate) fn remove(&mut self, key: TimerKey) {
+        self.entries.remove(&key);
+    }
+
+    /// Returns the deadline of the timer firing next.
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.entries.keys().next().map(|(deadline, _)| *deadline)
+    }
+
+    /// Removes the timers expired at `now` and adds their wakers to `wakers`.
+    pub(crate) fn expire(&mut self, now: Instant, wakers: &mut Vec<Waker>) {
+        while let Some(entry) = self.entries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            wakers.push(entry.remove().waker);
+        }
+    }
+
+    /// Returns the tasks waiting on a timer, along with its deadline.
+    pub(crate) fn parked(&self) -> impl Iterator<Item = (TaskId, Instant)> + '_ {
+        self.entries
+            .iter()
+            .filter_map(|((deadline, _), p)| Some((p.task?, *deadline)))
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+type Socket = std::os::unix::net::UnixStream;
+#[cfg(target_os = "wasi")]
+type Socket = wasmedge_wasi_socket::TcpStream;
+
+/// Interrupts a blocking wait of the reactor from any thread.
+///
+/// A connected pair of sockets serves as a self-pipe: the read end is
+/// registered with the poller, and a byte written to the other end makes the
+/// wait return. WASI has neither pipes nor eventfd, so the pair is a loopback
+/// TCP connection there.
+pub(crate) struct Wakeup {
+    sender: Mutex<Socket>,
+    receiver: Mutex<Socket>,
+    fd: RawFd,
+    /// Whether the reactor is waiting, or about to.
+    parked: AtomicBool,
+    /// Whether a byte was written and not drained yet.
+    signaled: AtomicBool,
+}
+
+/// Clears the parked flag of a [`Wakeup`] when dropped.
+pub(crate) struct ParkGuard<'a>(&'a Wakeup);
+
+impl Wakeup {
+    pub(crate) fn new() -> io::Result<Self> {
+        let (sender, receiver) = pair()?;
+        sender.set_nonblocking(true)?;
+        receiver.set_nonblocking(true)?;
+        Ok(Self {
+            fd: receiver.as_raw_fd(),
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+            parked: AtomicBool::new(false),
+            signaled: AtomicBool::new(false),
+        })
+    }
+
+    /// The fd to poll for readability.
+    pub(crate) fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Marks the reactor as parked until the guard is dropped.
+    ///
+    /// Callers must check for runnable work after this returns and skip the
+    /// wait if there is some; work queued afterwards interrupts the wait.
+    pub(crate) fn park(&self) -> ParkGuard<'_> {
+        self.parked.store(true, Ordering::SeqCst);
+        ParkGuard(self)
+    }
+
+    /// Interrupts the wait of the reactor, if it is parked.
+    pub(crate) fn notify(&self) {
+        if self.parked.load(Ordering::SeqCst) && !self.signaled.swap(true, Ordering::SeqCst) {
+            // A full socket buffer still wakes the reactor, so the result is
+            // irrelevant.
+            let _ = lock(&self.sender).write(&[1]);
+        }
+    }
+
+    /// Consumes pending wakeups once the reactor is awake.
+    pub(crate) fn drain(&self) {
+        self.signaled.store(false, Ordering::SeqCst);
+        let mut buf = [0; 64];
+        let mut receiver = lock(&self.receiver);
+        while let Ok(n) = receiver.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for ParkGuard<'_> {
+    fn drop(&mut self) {
+        self.0.parked.store(false, Ordering::SeqCst);
+    }
+}
+
+fn lock(stream: &Mutex<Socket>) -> std::sync::MutexGuard<'_, Socket> {
+    stream.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn pair() -> io::Result<(Socket, Socket)> {
+    Socket::pair()
+}
+
+#[cfg(target_os = "wasi")]
+fn pair() -> io::Result<(Socket, Socket)> {
+    let listener = wasmedge_wasi_socket::TcpListener::bind("127.0.0.1:0", false)?;
+    let sender = Socket::connect(listener.local_addr()?)?;
+    let local = sender.local_addr()?;
+    // Another process may connect to the listener in the meantime.
+    loop {
+        let (receiver, peer) = listener.accept(false)?;
+        if peer == local {
+            return Ok((sender, receiver));
+        }
+    }
+}
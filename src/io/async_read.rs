@@ -1,8 +1,9 @@
 use super::read_buf::ReadBuf;
+use crate::executor::coop;
 use std::io;
 use std::ops::DerefMut;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
 
 /// Reads bytes from a source.
 ///
@@ -94,9 +95,11 @@ where
 impl AsyncRead for &[u8] {
     fn poll_read(
         mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
+        // Always ready, so a reading loop has to be stopped by the budget.
+        ready!(coop::poll_proceed(cx)).made_progress();
         let amt = std::cmp::min(self.len(), buf.remaining());
         let (a, b) = self.split_at(amt);
         buf.put_slice(a);
@@ -108,9 +111,10 @@ impl AsyncRead for &[u8] {
 impl<T: AsRef<[u8]> + Unpin> AsyncRead for io::Cursor<T> {
     fn poll_read(
         mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         let pos = self.position();
         let slice: &[u8] = (*self).get_ref().as_ref();
 
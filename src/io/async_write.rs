@@ -1,7 +1,8 @@
+use crate::executor::coop;
 use std::io::{self, IoSlice};
 use std::ops::DerefMut;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
 
 /// Writes bytes asynchronously.
 ///
@@ -259,18 +260,21 @@ where
 impl AsyncWrite for Vec<u8> {
     fn poll_write(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        // Always ready, so a writing loop has to be stopped by the budget.
+        ready!(coop::poll_proceed(cx)).made_progress();
         self.get_mut().extend_from_slice(buf);
         Poll::Ready(Ok(buf.len()))
     }
 
     fn poll_write_vectored(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write_vectored(&mut *self, bufs))
     }
 
@@ -290,17 +294,19 @@ impl AsyncWrite for Vec<u8> {
 impl AsyncWrite for io::Cursor<&mut [u8]> {
     fn poll_write(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write(&mut *self, buf))
     }
 
     fn poll_write_vectored(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write_vectored(&mut *self, bufs))
     }
 
@@ -320,17 +326,19 @@ impl AsyncWrite for io::Cursor<&mut [u8]> {
 impl AsyncWrite for io::Cursor<&mut Vec<u8>> {
     fn poll_write(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write(&mut *self, buf))
     }
 
     fn poll_write_vectored(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write_vectored(&mut *self, bufs))
     }
 
@@ -350,17 +358,19 @@ impl AsyncWrite for io::Cursor<&mut Vec<u8>> {
 impl AsyncWrite for io::Cursor<Vec<u8>> {
     fn poll_write(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write(&mut *self, buf))
     }
 
     fn poll_write_vectored(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write_vectored(&mut *self, bufs))
     }
 
@@ -380,17 +390,19 @@ impl AsyncWrite for io::Cursor<Vec<u8>> {
 impl AsyncWrite for io::Cursor<Box<[u8]>> {
     fn poll_write(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write(&mut *self, buf))
     }
 
     fn poll_write_vectored(
         mut self: Pin<&mut Self>,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<io::Result<usize>> {
+        ready!(coop::poll_proceed(cx)).made_progress();
         Poll::Ready(io::Write::write_vectored(&mut *self, bufs))
     }
 
@@ -1,6 +1,14 @@
+#[cfg(not(any(target_os = "wasi", target_os = "linux", target_os = "android")))]
+compile_error!("wasmedge-async has an IO backend only for WASI (WasmEdge) and Linux (epoll)");
+
 pub mod executor;
 pub mod io;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod task;
 pub mod tcp;
+pub mod time;
+mod trace;
 pub use executor::*;
 pub use io::*;
 pub use tcp::*;
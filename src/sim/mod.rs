@@ -0,0 +1,329 @@
+//! Deterministic simulation of networked tasks.
+//!
+//! A [`Sim`] runs tasks on virtual hosts, each identified by an IP address,
+//! that talk to each other through an in-memory network instead of real
+//! sockets. [`TcpListener`](crate::TcpListener) and
+//! [`TcpStream`](crate::TcpStream) pick the simulated network when created
+//! by a task of a simulation, so the code under test runs unchanged.
+//!
+//! Time in a simulation is virtual: [`time::now`](crate::time::now),
+//! [`sleep`](crate::time::sleep) and the network all follow a clock that only
+//! moves when every task waits, and then jumps straight to the next timer or
+//! segment delivery. Latency, packet loss and the order queued tasks run in
+//! are drawn from a seeded generator, so a run is reproduced exactly by
+//! running it again with the same seed.
+//!
+//! ```ignore
+//! let mut sim = Sim::builder()
+//!     .seed(7)
+//!     .latency(Duration::from_millis(1), Duration::from_millis(50))
+//!     .packet_loss(0.05)
+//!     .build();
+//! sim.spawn([10, 0, 0, 1], || async { serve("0.0.0.0:80").await });
+//! let reply = sim.block_on([10, 0, 0, 2], || async {
+//!     sim::partition([10, 0, 0, 1], [10, 0, 0, 2]);
+//!     ...
+//! })?;
+//! ```
+
+mod net;
+
+pub(crate) use net::{SimTcpListener, SimTcpStream};
+
+use crate::executor::{Backend, Executor, Interest, JoinHandle};
+use net::Net;
+use pin_project_lite::pin_project;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// The network of the simulation running on this thread.
+    static CURRENT: RefCell<Option<Rc<RefCell<Net>>>> = const { RefCell::new(None) };
+    /// The host of the task being polled.
+    static HOST: Cell<Option<IpAddr>> = const { Cell::new(None) };
+}
+
+/// Configures and creates a [`Sim`].
+pub struct Builder {
+    seed: u64,
+    latency: (Duration, Duration),
+    packet_loss: f64,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            seed: 0,
+            latency: (Duration::from_millis(1), Duration::from_millis(1)),
+            packet_loss: 0.0,
+        }
+    }
+
+    /// Sets the seed that latencies, losses and the order of tasks are drawn
+    /// from. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the range the one-way latency of each segment is drawn from.
+    /// Defaults to 1ms.
+    pub fn latency(mut self, min: Duration, max: Duration) -> Self {
+        self.latency = (min, max.max(min));
+        self
+    }
+
+    /// Sets the probability that a segment is lost, between 0 and 1. A lost
+    /// segment is retransmitted after 200ms, delaying the segments behind it.
+    /// Defaults to 0.
+    pub fn packet_loss(mut self, probability: f64) -> Self {
+        self.packet_loss = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn build(self) -> Sim {
+        Sim {
+            executor: Some(Executor::builder().backend(SimBackend).build()),
+            net: Rc::new(RefCell::new(Net::new(
+                self.seed,
+                self.latency,
+                self.packet_loss,
+            ))),
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A simulated network of hosts and the executor running their tasks.
+///
+/// Tasks spawned by a task run on the host of their parent.
+pub struct Sim {
+    executor: Option<Executor>,
+    net: Rc<RefCell<Net>>,
+}
+
+impl Sim {
+    /// Creates a simulation with the given seed and default settings.
+    pub fn new(seed: u64) -> Self {
+        Builder::new().seed(seed).build()
+    }
+
+    /// Returns a [`Builder`] to configure a new simulation.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Spawns the future returned by `f` as a task on `host`. The task runs
+    /// once the simulation is driven by [`Sim::block_on`].
+    pub fn spawn<F, T>(&self, host: impl Into<IpAddr>, f: F) -> JoinHandle<T::Output>
+    where
+        F: FnOnce() -> T,
+        T: Future + 'static,
+        T::Output: 'static,
+    {
+        let _sim = self.enter();
+        let _host = HostGuard::enter(Some(host.into()));
+        self.executor().handle().spawn_local(f())
+    }
+
+    /// Runs the future returned by `f` on `host` to completion, driving the
+    /// tasks of the simulation in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Besides the errors of [`Executor::block_on`], fails if the simulation
+    /// stalls: the future waits while no task can run and no timer or
+    /// segment is pending, so it would wait forever.
+    pub fn block_on<F, T, O>(&mut self, host: impl Into<IpAddr>, f: F) -> io::Result<O>
+    where
+        F: Fn() -> T,
+        T: Future<Output = O> + 'static,
+    {
+        let host = Some(host.into());
+        let _sim = self.enter();
+        let executor = self.executor.as_mut().unwrap();
+        executor.block_on(|| {
+            let _host = HostGuard::enter(host);
+            OnHost { host, future: f() }
+        })
+    }
+
+    /// Returns the virtual time that has passed since the simulation was
+    /// created.
+    pub fn elapsed(&self) -> Duration {
+        self.net.borrow().elapsed()
+    }
+
+    /// Cuts the network between hosts `a` and `b`, see [`partition`].
+    pub fn partition(&self, a: impl Into<IpAddr>, b: impl Into<IpAddr>) {
+        self.net.borrow_mut().partition(a.into(), b.into());
+    }
+
+    /// Restores the network between hosts `a` and `b`, see [`heal`].
+    pub fn heal(&self, a: impl Into<IpAddr>, b: impl Into<IpAddr>) {
+        self.net.borrow_mut().heal(a.into(), b.into());
+    }
+
+    fn executor(&self) -> &Executor {
+        self.executor.as_ref().unwrap()
+    }
+
+    fn enter(&self) -> EnterGuard {
+        EnterGuard(CURRENT.with(|current| current.replace(Some(self.net.clone()))))
+    }
+}
+
+impl Drop for Sim {
+    fn drop(&mut self) {
+        // The sockets owned by the remaining tasks close on the network.
+        let _sim = self.enter();
+        self.executor.take();
+    }
+}
+
+struct EnterGuard(Option<Rc<RefCell<Net>>>);
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Cuts the network between hosts `a` and `b` of the current simulation.
+///
+/// Segments between them are held back, as if every transmission was lost,
+/// and are delivered once the partition [heals](heal). Connection attempts
+/// across the partition time out after 3 seconds.
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn partition(a: impl Into<IpAddr>, b: impl Into<IpAddr>) {
+    let (a, b) = (a.into(), b.into());
+    with_net(|net| net.partition(a, b)).expect("`partition` called outside of a simulation");
+}
+
+/// Restores the network between hosts `a` and `b` of the current simulation.
+///
+/// # Panics
+///
+/// Panics if called outside of a simulation.
+pub fn heal(a: impl Into<IpAddr>, b: impl Into<IpAddr>) {
+    let (a, b) = (a.into(), b.into());
+    with_net(|net| net.heal(a, b)).expect("`heal` called outside of a simulation");
+}
+
+/// Returns the host the current task runs on, if it runs in a simulation.
+pub fn current_host() -> Option<IpAddr> {
+    HOST.with(Cell::get)
+}
+
+fn with_net<R>(f: impl FnOnce(&mut Net) -> R) -> Option<R> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|net| f(&mut net.borrow_mut()))
+    })
+}
+
+/// Returns true if a simulation runs on this thread.
+pub(crate) fn is_active() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+/// Returns the virtual time of the current simulation.
+pub(crate) fn now() -> Option<Instant> {
+    with_net(|net| net.now())
+}
+
+/// Picks which of `n` queued tasks runs next, if a simulation runs.
+pub(crate) fn choose(n: usize) -> Option<usize> {
+    if n == 0 {
+        return None;
+    }
+    with_net(|net| net.choose(n))
+}
+
+/// Waits on the network of the simulation running on the calling thread.
+struct SimBackend;
+
+impl Backend for SimBackend {
+    fn register(&self, _fd: RawFd) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn deregister(&self, _fd: RawFd) {}
+
+    fn poll(
+        &self,
+        interests: &[(RawFd, Interest)],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(RawFd, Interest)>> {
+        with_net(|net| net.poll(interests, timeout)).unwrap_or_else(|| {
+            Err(io::Error::other(
+                "simulated reactor polled outside of its simulation",
+            ))
+        })
+    }
+
+    /// Only the tasks of the simulation, on its thread, queue tasks.
+    fn needs_wakeup(&self) -> bool {
+        false
+    }
+}
+
+struct HostGuard(Option<IpAddr>);
+
+impl HostGuard {
+    fn enter(host: Option<IpAddr>) -> Self {
+        HostGuard(HOST.with(|current| current.replace(host)))
+    }
+}
+
+impl Drop for HostGuard {
+    fn drop(&mut self) {
+        HOST.with(|current| current.set(self.0));
+    }
+}
+
+pin_project! {
+    /// Runs a future on a host of a simulation.
+    pub(crate) struct OnHost<F> {
+        host: Option<IpAddr>,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<F> OnHost<F> {
+    /// Runs `future` on the host of the current task.
+    pub(crate) fn current(future: F) -> Self {
+        OnHost {
+            host: current_host(),
+            future,
+        }
+    }
+}
+
+impl<F: Future> Future for OnHost<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.project();
+        let _host = HostGuard::enter(*this.host);
+        this.future.poll(cx)
+    }
+}
@@ -0,0 +1,658 @@
+//! The network of a simulation.
+//!
+//! Each connection is a pair of pipes, one per direction. A pipe delivers
+//! its segments in order, each one a random latency after it was sent, so a
+//! late segment holds back the ones behind it like a TCP receive window
+//! would. A lost segment is retransmitted, and only arrives late. Segments
+//! between partitioned hosts wait in their pipe until the partition heals.
+
+use super::{current_host, with_net};
+use crate::executor::Interest;
+use crate::tcp::{Shutdown, SocketAddr};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::os::fd::RawFd;
+use std::time::{Duration, Instant};
+
+/// How long a sender waits before retransmitting a lost segment.
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+/// How often a segment is lost at most before it gets through.
+const MAX_RETRANSMITS: u32 = 8;
+/// How long a connection attempt waits for the other side to answer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How many bytes a stream may have in flight before writes block.
+const SEND_BUFFER: usize = 64 * 1024;
+/// The first port handed out to sockets bound to port 0.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+pub(crate) struct Net {
+    rng: Rng,
+    latency: (Duration, Duration),
+    packet_loss: f64,
+    start: Instant,
+    elapsed: Duration,
+    next_fd: RawFd,
+    next_port: u16,
+    sockets: BTreeMap<RawFd, Socket>,
+    listeners: HashMap<SocketAddr, RawFd>,
+    pipes: BTreeMap<u64, Pipe>,
+    next_pipe: u64,
+    partitions: HashSet<(IpAddr, IpAddr)>,
+}
+
+enum Socket {
+    Listener(Listener),
+    Stream(Stream),
+}
+
+struct Listener {
+    addr: SocketAddr,
+    /// Connections established with the listener but not accepted yet.
+    backlog: VecDeque<RawFd>,
+}
+
+struct Stream {
+    local: SocketAddr,
+    peer: SocketAddr,
+    state: State,
+    /// The pipe carrying what the stream sends.
+    pipe: u64,
+    recv: VecDeque<u8>,
+    /// Bytes sent that the peer has not received yet.
+    in_flight: usize,
+    fin_sent: bool,
+    fin_received: bool,
+    /// When a connection attempt times out.
+    connect_deadline: Duration,
+}
+
+enum State {
+    Connecting,
+    Connected,
+    Failed(io::ErrorKind),
+}
+
+/// One direction of a connection.
+struct Pipe {
+    src: IpAddr,
+    dst: IpAddr,
+    to: Dest,
+    /// The socket sending on the pipe, until it is closed.
+    owner: Option<RawFd>,
+    /// When the last segment sent is delivered.
+    last: Duration,
+    segments: VecDeque<(Duration, Segment)>,
+}
+
+#[derive(Clone, Copy)]
+enum Dest {
+    Listener(SocketAddr),
+    Socket(RawFd),
+}
+
+enum Segment {
+    Syn { from: RawFd, addr: SocketAddr },
+    SynAck { from: RawFd },
+    Rst,
+    Data(Vec<u8>),
+    Fin,
+}
+
+impl Net {
+    pub(crate) fn new(seed: u64, latency: (Duration, Duration), packet_loss: f64) -> Self {
+        Self {
+            rng: Rng(seed),
+            latency,
+            packet_loss,
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+            next_fd: 3,
+            next_port: FIRST_EPHEMERAL_PORT,
+            sockets: BTreeMap::new(),
+            listeners: HashMap::new(),
+            pipes: BTreeMap::new(),
+            next_pipe: 0,
+            partitions: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        self.start + self.elapsed
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Picks an index below `n`.
+    pub(crate) fn choose(&mut self, n: usize) -> usize {
+        (self.rng.next() % n as u64) as usize
+    }
+
+    pub(crate) fn partition(&mut self, a: IpAddr, b: IpAddr) {
+        self.partitions.insert(pair(a, b));
+    }
+
+    pub(crate) fn heal(&mut self, a: IpAddr, b: IpAddr) {
+        self.partitions.remove(&pair(a, b));
+    }
+
+    fn is_partitioned(&self, a: IpAddr, b: IpAddr) -> bool {
+        a != b && self.partitions.contains(&pair(a, b))
+    }
+
+    /// Waits like [`Backend::poll`](crate::executor::Backend::poll),
+    /// advancing the virtual time as far as needed.
+    pub(crate) fn poll(
+        &mut self,
+        interests: &[(RawFd, Interest)],
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(RawFd, Interest)>> {
+        let deadline = timeout.map(|timeout| self.elapsed + timeout);
+        // Interests come out of a hash set, whose order must not leak into the
+        // order tasks are woken in.
+        let mut interests = interests.to_vec();
+        interests.sort_unstable_by_key(|&(fd, _)| fd);
+        loop {
+            self.deliver_due();
+            let events = self.ready(&interests);
+            if !events.is_empty() || deadline.is_some_and(|d| d <= self.elapsed) {
+                return Ok(events);
+            }
+            let next = match (self.next_event(), deadline) {
+                (Some(next), Some(deadline)) => next.min(deadline),
+                (next, deadline) => next.or(deadline).ok_or_else(|| {
+                    io::Error::other(
+                        "simulation stalled: every task waits, \
+                         and no timer or segment is pending",
+                    )
+                })?,
+            };
+            self.elapsed = self.elapsed.max(next);
+        }
+    }
+
+    fn ready(&self, interests: &[(RawFd, Interest)]) -> Vec<(RawFd, Interest)> {
+        let mut events = Vec::new();
+        for &(fd, interest) in interests {
+            let (readable, writable) = match self.sockets.get(&fd) {
+                Some(Socket::Listener(listener)) => (!listener.backlog.is_empty(), false),
+                Some(Socket::Stream(stream)) => match stream.state {
+                    State::Connecting => (false, false),
+                    State::Connected => (
+                        !stream.recv.is_empty() || stream.fin_received,
+                        stream.fin_sent || stream.in_flight < SEND_BUFFER,
+                    ),
+                    State::Failed(_) => (true, true),
+                },
+                None => (false, false),
+            };
+            let readable = readable && interest != Interest::Write;
+            let writable = writable && interest != Interest::Read;
+            match (readable, writable) {
+                (true, true) => events.push((fd, Interest::All)),
+                (true, false) => events.push((fd, Interest::Read)),
+                (false, true) => events.push((fd, Interest::Write)),
+                (false, false) => {}
+            }
+        }
+        events
+    }
+
+    /// Returns when the next segment is delivered or connection attempt
+    /// times out, ignoring segments held back by a partition.
+    fn next_event(&self) -> Option<Duration> {
+        let deliveries = self
+            .pipes
+            .values()
+            .filter(|pipe| !self.is_partitioned(pipe.src, pipe.dst))
+            .filter_map(|pipe| pipe.segments.front().map(|(at, _)| *at));
+        let timeouts = self.sockets.values().filter_map(|socket| match socket {
+            Socket::Stream(stream) if matches!(stream.state, State::Connecting) => {
+                Some(stream.connect_deadline)
+            }
+            _ => None,
+        });
+        deliveries.chain(timeouts).min()
+    }
+
+    /// Delivers the segments that are due, in the order they arrive.
+    fn deliver_due(&mut self) {
+        loop {
+            let next = self
+                .pipes
+                .iter()
+                .filter(|(_, pipe)| !self.is_partitioned(pipe.src, pipe.dst))
+                .filter_map(|(id, pipe)| pipe.segments.front().map(|(at, _)| (*at, *id)))
+                .filter(|(at, _)| *at <= self.elapsed)
+                .min();
+            let id = match next {
+                Some((_, id)) => id,
+                None => break,
+            };
+            let pipe = self.pipes.get_mut(&id).unwrap();
+            let (_, segment) = pipe.segments.pop_front().unwrap();
+            let (to, owner) = (pipe.to, pipe.owner);
+            self.receive(id, to, owner, segment);
+            self.remove_if_unused(id);
+        }
+        let elapsed = self.elapsed;
+        let mut timed_out = Vec::new();
+        for socket in self.sockets.values_mut() {
+            if let Socket::Stream(stream) = socket {
+                if matches!(stream.state, State::Connecting) && stream.connect_deadline <= elapsed {
+                    stream.state = State::Failed(io::ErrorKind::TimedOut);
+                    timed_out.push(stream.pipe);
+                }
+            }
+        }
+        for pipe in timed_out {
+            if let Some(pipe) = self.pipes.get_mut(&pipe) {
+                pipe.segments.clear();
+            }
+        }
+    }
+
+    fn receive(&mut self, pipe: u64, to: Dest, owner: Option<RawFd>, segment: Segment) {
+        match (to, segment) {
+            (Dest::Listener(addr), Segment::Syn { from, addr: peer }) => {
+                match self.listeners.get(&addr) {
+                    Some(&listener) => {
+                        let fd = self.open(addr, peer, State::Connected, Dest::Socket(from));
+                        if let Some(Socket::Listener(listener)) = self.sockets.get_mut(&listener) {
+                            listener.backlog.push_back(fd);
+                        }
+                        let pipe = self.stream(fd).unwrap().pipe;
+                        self.send(pipe, Segment::SynAck { from: fd });
+                    }
+                    None => {
+                        let rst = self.pipe(addr.ip(), peer.ip(), Dest::Socket(from), None);
+                        self.send(rst, Segment::Rst);
+                    }
+                }
+            }
+            (Dest::Socket(fd), segment) => {
+                if let Segment::Data(data) = &segment {
+                    if let Some(sender) = owner.and_then(|owner| self.stream(owner)) {
+                        sender.in_flight -= data.len();
+                    }
+                }
+                // Segments for a closed socket are dropped.
+                let stream = match self.stream(fd) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+                match segment {
+                    Segment::SynAck { from } => {
+                        if matches!(stream.state, State::Connecting) {
+                            stream.state = State::Connected;
+                            let pipe = stream.pipe;
+                            self.pipes.get_mut(&pipe).unwrap().to = Dest::Socket(from);
+                        }
+                    }
+                    Segment::Rst => {
+                        if matches!(stream.state, State::Connecting) {
+                            stream.state = State::Failed(io::ErrorKind::ConnectionRefused);
+                        }
+                    }
+                    Segment::Data(data) => stream.recv.extend(data),
+                    Segment::Fin => stream.fin_received = true,
+                    Segment::Syn { .. } => {}
+                }
+            }
+            (Dest::Listener(_), _) => unreachable!("pipe {} to a listener carries data", pipe),
+        }
+    }
+
+    fn send(&mut self, pipe: u64, segment: Segment) {
+        let mut delay = self.rng.between(self.latency.0, self.latency.1);
+        let mut retransmits = 0;
+        while retransmits < MAX_RETRANSMITS && self.rng.chance(self.packet_loss) {
+            delay += RETRANSMIT_TIMEOUT;
+            retransmits += 1;
+        }
+        let pipe = self.pipes.get_mut(&pipe).unwrap();
+        let at = (self.elapsed + delay).max(pipe.last);
+        pipe.last = at;
+        pipe.segments.push_back((at, segment));
+    }
+
+    fn pipe(&mut self, src: IpAddr, dst: IpAddr, to: Dest, owner: Option<RawFd>) -> u64 {
+        let id = self.next_pipe;
+        self.next_pipe += 1;
+        self.pipes.insert(
+            id,
+            Pipe {
+                src,
+                dst,
+                to,
+                owner,
+                last: Duration::ZERO,
+                segments: VecDeque::new(),
+            },
+        );
+        id
+    }
+
+    fn remove_if_unused(&mut self, pipe: u64) {
+        if let Some(p) = self.pipes.get(&pipe) {
+            if p.owner.is_none() && p.segments.is_empty() {
+                self.pipes.remove(&pipe);
+            }
+        }
+    }
+
+    /// Creates a stream sending to `to`.
+    fn open(&mut self, local: SocketAddr, peer: SocketAddr, state: State, to: Dest) -> RawFd {
+        let fd = self.next_fd();
+        let pipe = self.pipe(local.ip(), peer.ip(), to, Some(fd));
+        let stream = Stream {
+            local,
+            peer,
+            state,
+            pipe,
+            recv: VecDeque::new(),
+            in_flight: 0,
+            fin_sent: false,
+            fin_received: false,
+            connect_deadline: self.elapsed + CONNECT_TIMEOUT,
+        };
+        self.sockets.insert(fd, Socket::Stream(stream));
+        fd
+    }
+
+    fn next_fd(&mut self) -> RawFd {
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        fd
+    }
+
+    fn ephemeral_port(&mut self) -> u16 {
+        let port = self.next_port;
+        self.next_port = self
+            .next_port
+            .checked_add(1)
+            .unwrap_or(FIRST_EPHEMERAL_PORT);
+        port
+    }
+
+    fn stream(&mut self, fd: RawFd) -> Option<&mut Stream> {
+        match self.sockets.get_mut(&fd) {
+            Some(Socket::Stream(stream)) => Some(stream),
+            _ => None,
+        }
+    }
+
+    fn stream_or_err(&mut self, fd: RawFd) -> io::Result<&mut Stream> {
+        self.stream(fd)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+    }
+
+    fn bind(&mut self, host: IpAddr, addr: SocketAddr) -> io::Result<RawFd> {
+        let mut addr = local_to(host, addr);
+        if addr.port() == 0 {
+            addr.set_port(self.ephemeral_port());
+        }
+        if self.listeners.contains_key(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+        let fd = self.next_fd();
+        self.listeners.insert(addr, fd);
+        let backlog = VecDeque::new();
+        self.sockets
+            .insert(fd, Socket::Listener(Listener { addr, backlog }));
+        Ok(fd)
+    }
+
+    fn accept(&mut self, fd: RawFd) -> io::Result<(RawFd, SocketAddr)> {
+        let stream = match self.sockets.get_mut(&fd) {
+            Some(Socket::Listener(listener)) => listener.backlog.pop_front(),
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        match stream {
+            Some(stream) => Ok((stream, self.stream(stream).unwrap().peer)),
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn connect(&mut self, host: IpAddr, addr: SocketAddr) -> RawFd {
+        let addr = local_to(host, addr);
+        let local = SocketAddr::new(host, self.ephemeral_port());
+        let fd = self.open(local, addr, State::Connecting, Dest::Listener(addr));
+        let pipe = self.stream(fd).unwrap().pipe;
+        self.send(
+            pipe,
+            Segment::Syn {
+                from: fd,
+                addr: local,
+            },
+        );
+        fd
+    }
+
+    fn read(&mut self, fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = self.stream_or_err(fd)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if !stream.recv.is_empty() {
+            let n = buf.len().min(stream.recv.len());
+            for (dst, src) in buf.iter_mut().zip(stream.recv.drain(..n)) {
+                *dst = src;
+            }
+            return Ok(n);
+        }
+        match stream.state {
+            State::Failed(kind) => Err(kind.into()),
+            State::Connected if stream.fin_received => Ok(0),
+            _ => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn write(&mut self, fd: RawFd, buf: &[u8]) -> io::Result<usize> {
+        let stream = self.stream_or_err(fd)?;
+        match stream.state {
+            State::Failed(kind) => return Err(kind.into()),
+            State::Connecting => return Err(io::ErrorKind::WouldBlock.into()),
+            State::Connected if stream.fin_sent => return Err(io::ErrorKind::BrokenPipe.into()),
+            State::Connected => {}
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = buf.len().min(SEND_BUFFER - stream.in_flight);
+        if n == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        stream.in_flight += n;
+        let pipe = stream.pipe;
+        self.send(pipe, Segment::Data(buf[..n].to_vec()));
+        Ok(n)
+    }
+
+    fn shutdown(&mut self, fd: RawFd, how: Shutdown) -> io::Result<()> {
+        let stream = self.stream_or_err(fd)?;
+        match stream.state {
+            State::Connected => {}
+            State::Connecting => return Err(io::ErrorKind::NotConnected.into()),
+            State::Failed(kind) => return Err(kind.into()),
+        }
+        if how == Shutdown::Read || stream.fin_sent {
+            return Ok(());
+        }
+        stream.fin_sent = true;
+        let pipe = stream.pipe;
+        self.send(pipe, Segment::Fin);
+        Ok(())
+    }
+
+    fn close(&mut self, fd: RawFd) {
+        match self.sockets.remove(&fd) {
+            Some(Socket::Listener(listener)) => {
+                self.listeners.remove(&listener.addr);
+                for stream in listener.backlog {
+                    self.close(stream);
+                }
+            }
+            Some(Socket::Stream(stream)) => {
+                let pipe = self.pipes.get_mut(&stream.pipe).unwrap();
+                pipe.owner = None;
+                match stream.state {
+                    // The connection attempt is abandoned.
+                    State::Connecting => pipe.segments.clear(),
+                    State::Connected if !stream.fin_sent => self.send(stream.pipe, Segment::Fin),
+                    _ => {}
+                }
+                self.remove_if_unused(stream.pipe);
+            }
+            None => {}
+        }
+    }
+
+    fn local_addr(&self, fd: RawFd) -> io::Result<SocketAddr> {
+        match self.sockets.get(&fd) {
+            Some(Socket::Listener(listener)) => Ok(listener.addr),
+            Some(Socket::Stream(stream)) => Ok(stream.local),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn peer_addr(&self, fd: RawFd) -> io::Result<SocketAddr> {
+        match self.sockets.get(&fd) {
+            Some(Socket::Stream(stream)) => Ok(stream.peer),
+            _ => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+/// Maps the loopback and unspecified addresses to `host`, which every host
+/// reaches itself under.
+fn local_to(host: IpAddr, mut addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_loopback() || addr.ip().is_unspecified() {
+        addr.set_ip(host);
+    }
+    addr
+}
+
+fn pair(a: IpAddr, b: IpAddr) -> (IpAddr, IpAddr) {
+    (a.min(b), a.max(b))
+}
+
+/// SplitMix64, small and good enough to pick latencies and orders from.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        p > 0.0 && ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn between(&mut self, min: Duration, max: Duration) -> Duration {
+        if max <= min {
+            return min;
+        }
+        let range = (max - min).as_nanos() as u64;
+        min + Duration::from_nanos(self.next() % (range + 1))
+    }
+}
+
+fn with_sim<R>(f: impl FnOnce(&mut Net) -> io::Result<R>) -> io::Result<R> {
+    with_net(f).unwrap_or_else(|| {
+        Err(io::Error::other(
+            "simulated socket used outside of its simulation",
+        ))
+    })
+}
+
+fn host() -> IpAddr {
+    current_host().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// A listener of the simulated network.
+pub(crate) struct SimTcpListener {
+    fd: RawFd,
+}
+
+impl SimTcpListener {
+    pub(crate) fn bind(addr: SocketAddr) -> io::Result<Self> {
+        with_sim(|net| net.bind(host(), addr)).map(|fd| Self { fd })
+    }
+
+    pub(crate) fn accept(&self) -> io::Result<(SimTcpStream, SocketAddr)> {
+        let (fd, addr) = with_sim(|net| net.accept(self.fd))?;
+        Ok((SimTcpStream { fd }, addr))
+    }
+
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        with_sim(|net| net.local_addr(self.fd))
+    }
+
+    pub(crate) fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for SimTcpListener {
+    fn drop(&mut self) {
+        with_net(|net| net.close(self.fd));
+    }
+}
+
+/// A stream of the simulated network.
+pub(crate) struct SimTcpStream {
+    fd: RawFd,
+}
+
+impl SimTcpStream {
+    /// Starts connecting to `addr`. The stream becomes writable once the
+    /// connection is established.
+    pub(crate) fn connect(addr: SocketAddr) -> io::Result<Self> {
+        with_sim(|net| Ok(net.connect(host(), addr))).map(|fd| Self { fd })
+    }
+
+    pub(crate) fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        with_sim(|net| net.shutdown(self.fd, how))
+    }
+
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        with_sim(|net| net.local_addr(self.fd))
+    }
+
+    pub(crate) fn peer_addr(&self) -> io::Result<SocketAddr> {
+        with_sim(|net| net.peer_addr(self.fd))
+    }
+
+    pub(crate) fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl io::Read for SimTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        with_sim(|net| net.read(self.fd, buf))
+    }
+}
+
+impl io::Write for SimTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        with_sim(|net| net.write(self.fd, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SimTcpStream {
+    fn drop(&mut self) {
+        with_net(|net| net.close(self.fd));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::executor::{handle, Handle, JoinHandle, Priority};
+use std::future::Future;
+
+/// Configures a task before it is spawned.
+///
+/// ```ignore
+/// task::Builder::new()
+///     .name("conn-42")
+///     .priority(Priority::Low)
+///     .spawn(handle_connection(stream));
+/// ```
+#[derive(Debug, Default)]
+pub struct Builder<'a> {
+    name: Option<&'a str>,
+    priority: Priority,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            priority: Priority::Normal,
+        }
+    }
+
+    /// Sets the name of the task, reported by [`current`](super::current)
+    /// and passed to the executor's task hooks.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the scheduling priority of the task, [`Priority::Normal`] by
+    /// default.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Spawns the task onto the current executor.
+    ///
+    /// With the `multi-thread` feature, the task is spawned onto the
+    /// multi-threaded runtime instead when called from one of its tasks,
+    /// which ignores the priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the executor context.
+    pub fn spawn<F>(self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        #[cfg(feature = "multi-thread")]
+        if let Some(handle) = crate::executor::multi_thread_handle() {
+            return handle.spawn_named(future, self.name);
+        }
+        self.spawn_local(future)
+    }
+
+    /// Spawns a task that is not `Send` onto the current executor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the executor context, which includes the
+    /// tasks of the multi-threaded runtime.
+    pub fn spawn_local<F>(self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        handle::with_current(|handle| handle.shared.spawn_local(future, self.name, self.priority))
+    }
+
+    /// Spawns the task onto the executor of `handle`.
+    pub fn spawn_on<F>(self, future: F, handle: &Handle) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        handle.shared.spawn_local(future, self.name, self.priority)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::executor::TaskId;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+thread_local! {
+    static CURRENT: RefCell<Option<TaskInfo>> = const { RefCell::new(None) };
+}
+
+/// The id and name of a spawned task.
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    id: TaskId,
+    name: Option<Arc<str>>,
+}
+
+impl TaskInfo {
+    pub(crate) fn new(id: TaskId, name: Option<&str>) -> Self {
+        Self {
+            id,
+            name: name.map(Arc::from),
+        }
+    }
+
+    /// Returns the id of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Returns the name given to the task with [`Builder::name`], if any.
+    ///
+    /// [`Builder::name`]: super::Builder::name
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl fmt::Display for TaskInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} (task {})", name, self.id),
+            None => write!(f, "task {}", self.id),
+        }
+    }
+}
+
+/// Returns the task that is currently being polled.
+///
+/// # Panics
+///
+/// Panics if called outside of a spawned task, e.g. from the future passed to
+/// `block_on`.
+pub fn current() -> TaskInfo {
+    try_current().expect("`task::current()` called outside of a spawned task")
+}
+
+/// Returns the task that is currently being polled, or `None` if called
+/// outside of a spawned task.
+pub fn try_current() -> Option<TaskInfo> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Returns the id of the task that is currently being polled.
+pub(crate) fn current_id() -> Option<TaskId> {
+    CURRENT.with(|current| current.borrow().as_ref().map(TaskInfo::id))
+}
+
+/// Makes `info` the current task while running `f`.
+pub(crate) fn enter<R>(info: &TaskInfo, f: impl FnOnce() -> R) -> R {
+    struct ResetGuard(Option<TaskInfo>);
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = prev);
+        }
+    }
+
+    let prev = CURRENT.with(|current| current.borrow_mut().replace(info.clone()));
+    let _guard = ResetGuard(prev);
+    f()
+}
@@ -0,0 +1,117 @@
+use crate::executor::{spawn, spawn_local, JoinError, JoinHandle};
+use futures::future::poll_fn;
+use futures::stream::{FuturesUnordered, Stream};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A collection of tasks spawned on the current executor.
+///
+/// Results are returned by [`JoinSet::join_next`] in the order the tasks
+/// complete. All tasks still in the set are aborted when it is dropped.
+///
+/// ```ignore
+/// let mut set = JoinSet::new();
+/// while let Some(ret) = listener.next().await {
+///     let (stream, _) = ret?;
+///     set.spawn(handle_connection(stream));
+/// }
+/// while let Some(res) = set.join_next().await {
+///     res?;
+/// }
+/// ```
+pub struct JoinSet<T> {
+    inner: FuturesUnordered<JoinHandle<T>>,
+}
+
+impl<T> JoinSet<T> {
+    pub fn new() -> Self {
+        Self {
+            inner: FuturesUnordered::new(),
+        }
+    }
+
+    /// Returns the number of tasks in the set.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the set contains no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Waits for one of the tasks in the set to complete and returns its
+    /// output, or `None` if the set is empty.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    /// Polls for one of the tasks in the set to complete.
+    pub fn poll_join_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, JoinError>>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+
+    /// Aborts all tasks in the set.
+    ///
+    /// The tasks stay in the set and are returned by
+    /// [`JoinSet::join_next`] with a cancelled [`JoinError`].
+    pub fn abort_all(&mut self) {
+        for handle in self.inner.iter() {
+            handle.abort();
+        }
+    }
+
+    /// Aborts all tasks and waits for them to finish.
+    pub async fn shutdown(&mut self) {
+        self.abort_all();
+        while self.join_next().await.is_some() {}
+    }
+}
+
+impl<T: 'static> JoinSet<T> {
+    /// Spawns a task on the current executor and adds it to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the executor context.
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send,
+    {
+        self.inner.push(spawn(future));
+    }
+
+    /// Spawns a future that is not `Send` on the current executor and adds
+    /// it to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the executor context.
+    pub fn spawn_local<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + 'static,
+    {
+        self.inner.push(spawn_local(future));
+    }
+}
+
+impl<T> Default for JoinSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for JoinSet<T> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+impl<T> fmt::Debug for JoinSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinSet").field("len", &self.len()).finish()
+    }
+}
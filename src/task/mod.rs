@@ -0,0 +1,12 @@
+//! Utilities for working with spawned tasks.
+
+mod builder;
+mod current;
+mod join_set;
+mod task_local;
+pub use crate::executor::{Priority, TaskId};
+pub use builder::Builder;
+pub use current::{current, try_current, TaskInfo};
+pub(crate) use current::{current_id, enter};
+pub use join_set::JoinSet;
+pub use task_local::{AccessError, LocalKey, TaskLocalFuture};
@@ -0,0 +1,195 @@
+use pin_project_lite::pin_project;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Declares a new task-local key of type [`LocalKey`].
+///
+/// A task-local value is set for the duration of a future with
+/// [`LocalKey::scope`] and is visible every time the executor polls that
+/// future, following it across `.await` points.
+///
+/// ```ignore
+/// wasmedge_async::task_local! {
+///     static REQUEST_ID: u64;
+/// }
+///
+/// REQUEST_ID
+///     .scope(42, async {
+///         assert_eq!(REQUEST_ID.get(), 42);
+///     })
+///     .await;
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __task_local_inner {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = {
+            std::thread_local! {
+                static __KEY: std::cell::RefCell<Option<$t>> = const { std::cell::RefCell::new(None) };
+            }
+
+            $crate::task::LocalKey { inner: __KEY }
+        };
+    };
+}
+
+/// A key for task-local data, declared with the [`task_local!`] macro.
+///
+/// [`task_local!`]: crate::task_local
+pub struct LocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub inner: std::thread::LocalKey<RefCell<Option<T>>>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    /// Sets the value of the task-local for the duration of `f`.
+    ///
+    /// The value is set every time the returned future is polled and
+    /// restored afterwards, so it follows `f` across `.await` points.
+    pub fn scope<F>(&'static self, value: T, f: F) -> TaskLocalFuture<T, F>
+    where
+        F: Future,
+    {
+        TaskLocalFuture {
+            local: self,
+            slot: Some(value),
+            future: f,
+        }
+    }
+
+    /// Sets the value of the task-local while running the closure `f`.
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut slot = Some(value);
+        self.scope_inner(&mut slot, f)
+    }
+
+    /// Accesses the current value of the task-local.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task-local is not set by an enclosing
+    /// [`LocalKey::scope`].
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        match self.try_with(f) {
+            Ok(ret) => ret,
+            Err(_) => panic!("cannot access a task-local value without setting it first"),
+        }
+    }
+
+    /// Accesses the current value of the task-local, or returns an error if
+    /// it is not set.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.inner
+            .try_with(|cell| cell.borrow().as_ref().map(f))
+            .ok()
+            .flatten()
+            .ok_or(AccessError { _private: () })
+    }
+
+    fn scope_inner<F, R>(&'static self, slot: &mut Option<T>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        // Puts the previous value back into the slot even if `f` panics.
+        struct Guard<'a, T: 'static> {
+            local: &'static LocalKey<T>,
+            slot: &'a mut Option<T>,
+        }
+
+        impl<T: 'static> Drop for Guard<'_, T> {
+            fn drop(&mut self) {
+                self.local.inner.with(|cell| {
+                    std::mem::swap(self.slot, &mut *cell.borrow_mut());
+                });
+            }
+        }
+
+        self.inner
+            .with(|cell| std::mem::swap(slot, &mut *cell.borrow_mut()));
+        let _guard = Guard { local: self, slot };
+        f()
+    }
+}
+
+impl<T: Clone + 'static> LocalKey<T> {
+    /// Returns a copy of the current value of the task-local.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task-local is not set by an enclosing
+    /// [`LocalKey::scope`].
+    pub fn get(&'static self) -> T {
+        self.with(T::clone)
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+pin_project! {
+    /// A future that sets a task-local value every time it is polled.
+    ///
+    /// Created by [`LocalKey::scope`].
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct TaskLocalFuture<T: 'static, F> {
+        local: &'static LocalKey<T>,
+        slot: Option<T>,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.project();
+        let future = me.future;
+        me.local.scope_inner(me.slot, || future.poll(cx))
+    }
+}
+
+/// The error returned by [`LocalKey::try_with`] when the task-local is not
+/// set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessError {
+    _private: (),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt("task-local value not set", f)
+    }
+}
+
+impl std::error::Error for AccessError {}
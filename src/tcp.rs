@@ -1,37 +1,235 @@
+use crate::executor::coop;
+use crate::executor::registration::Registration;
 use crate::io::{AsyncRead, AsyncWrite, ReadBuf};
-use crate::{Interest, EXECUTOR};
+use crate::trace::trace;
+use crate::Interest;
 use futures::Stream;
 use std::io;
-use std::os::wasi::io::AsRawFd;
+use std::os::fd::{AsRawFd, RawFd};
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use wasmedge_wasi_socket::TcpListener as WasiTcpListener;
-use wasmedge_wasi_socket::TcpStream as WasiTcpStream;
-use wasmedge_wasi_socket::{Shutdown, SocketAddr, ToSocketAddrs};
+
+#[cfg(not(target_os = "wasi"))]
+pub(crate) use std::net::{Shutdown, SocketAddr, ToSocketAddrs};
+#[cfg(not(target_os = "wasi"))]
+use std::net::{TcpListener as OsTcpListener, TcpStream as OsTcpStream};
+#[cfg(target_os = "wasi")]
+pub(crate) use wasmedge_wasi_socket::{Shutdown, SocketAddr, ToSocketAddrs};
+#[cfg(target_os = "wasi")]
+use wasmedge_wasi_socket::{TcpListener as OsTcpListener, TcpStream as OsTcpStream};
+
+#[cfg(feature = "sim")]
+use crate::sim::{self, SimTcpListener, SimTcpStream};
+
+/// The socket behind a [`TcpListener`]: a WasmEdge socket on WASI and a std
+/// socket elsewhere, or a socket of the simulated network when created within
+/// a [simulation](crate::sim).
+enum ListenerInner {
+    Os(OsTcpListener),
+    #[cfg(feature = "sim")]
+    Sim(SimTcpListener),
+}
+
+impl ListenerInner {
+    #[cfg_attr(not(target_os = "wasi"), allow(unused_variables))]
+    fn bind<A: ToSocketAddrs>(addrs: A, nonblocking: bool) -> io::Result<Self> {
+        #[cfg(feature = "sim")]
+        if sim::is_active() {
+            return SimTcpListener::bind(resolve(addrs)?).map(ListenerInner::Sim);
+        }
+        #[cfg(target_os = "wasi")]
+        let inner = OsTcpListener::bind(addrs, nonblocking)?;
+        // The std listener stays non-blocking, so that polling it as a
+        // `Stream` cannot block the executor, and `accept` blocks per call.
+        #[cfg(not(target_os = "wasi"))]
+        let inner = OsTcpListener::bind(addrs)?;
+        #[cfg(not(target_os = "wasi"))]
+        inner.set_nonblocking(true)?;
+        Ok(ListenerInner::Os(inner))
+    }
+
+    fn accept(&self, nonblocking: bool) -> io::Result<(StreamInner, SocketAddr)> {
+        match self {
+            ListenerInner::Os(inner) => {
+                #[cfg(target_os = "wasi")]
+                let (stream, addr) = inner.accept(nonblocking)?;
+                #[cfg(not(target_os = "wasi"))]
+                let (stream, addr) = accept_std(inner, nonblocking)?;
+                Ok((StreamInner::Os(stream), addr))
+            }
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(inner) => inner
+                .accept()
+                .map(|(stream, addr)| (StreamInner::Sim(stream), addr)),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            ListenerInner::Os(inner) => inner.local_addr(),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(inner) => inner.local_addr(),
+        }
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            ListenerInner::Os(inner) => inner.as_raw_fd(),
+            #[cfg(feature = "sim")]
+            ListenerInner::Sim(inner) => inner.as_raw_fd(),
+        }
+    }
+}
+
+/// Accepts a connection on a non-blocking std listener, waiting for one unless
+/// `nonblocking` is set, and gives the stream the same mode, like `accept` of
+/// WasmEdge.
+#[cfg(not(target_os = "wasi"))]
+fn accept_std(
+    listener: &OsTcpListener,
+    nonblocking: bool,
+) -> io::Result<(OsTcpStream, SocketAddr)> {
+    if !nonblocking {
+        listener.set_nonblocking(false)?;
+    }
+    let accepted = listener.accept();
+    if !nonblocking {
+        listener.set_nonblocking(true)?;
+    }
+    let (stream, addr) = accepted?;
+    stream.set_nonblocking(nonblocking)?;
+    Ok((stream, addr))
+}
+
+/// The socket behind a [`TcpStream`], see [`ListenerInner`].
+enum StreamInner {
+    Os(OsTcpStream),
+    #[cfg(feature = "sim")]
+    Sim(SimTcpStream),
+}
+
+impl StreamInner {
+    fn connect<A: ToSocketAddrs>(addrs: A) -> io::Result<Self> {
+        #[cfg(feature = "sim")]
+        if sim::is_active() {
+            return SimTcpStream::connect(resolve(addrs)?).map(StreamInner::Sim);
+        }
+        let inner = OsTcpStream::connect(addrs)?;
+        inner.set_nonblocking(true)?;
+        Ok(StreamInner::Os(inner))
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            StreamInner::Os(inner) => inner.shutdown(how),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.shutdown(how),
+        }
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            StreamInner::Os(inner) => inner.peer_addr(),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.peer_addr(),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            StreamInner::Os(inner) => inner.local_addr(),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.local_addr(),
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            StreamInner::Os(inner) => inner.set_nonblocking(nonblocking),
+            // Simulated streams never block.
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(_) => Ok(()),
+        }
+    }
+
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            StreamInner::Os(inner) => inner.as_raw_fd(),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.as_raw_fd(),
+        }
+    }
+}
+
+impl io::Read for StreamInner {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            StreamInner::Os(inner) => inner.read(buf),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.read(buf),
+        }
+    }
+}
+
+impl io::Write for StreamInner {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            StreamInner::Os(inner) => inner.write(buf),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            StreamInner::Os(inner) => inner.flush(),
+            #[cfg(feature = "sim")]
+            StreamInner::Sim(inner) => inner.flush(),
+        }
+    }
+}
+
+/// Returns the first address `addrs` resolves to.
+#[cfg(feature = "sim")]
+fn resolve<A: ToSocketAddrs>(addrs: A) -> io::Result<SocketAddr> {
+    addrs.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    })
+}
 
 pub struct TcpListener {
-    inner: WasiTcpListener,
+    // Deregisters before the socket is closed, so that the fd cannot be
+    // reused by another socket in between.
+    registration: Registration,
+    inner: ListenerInner,
 }
 
 impl TcpListener {
     pub fn bind<A: ToSocketAddrs>(addrs: A, nonblocking: bool) -> io::Result<TcpListener> {
-        match WasiTcpListener::bind(addrs, nonblocking) {
-            Ok(inner) => {
-                EXECUTOR.with(|ex| {
-                    ex.reactor.borrow_mut().add(inner.as_raw_fd());
-                });
-                Ok(TcpListener { inner })
-            }
+        match ListenerInner::bind(addrs, nonblocking) {
+            Ok(inner) => Ok(TcpListener {
+                registration: Registration::new(inner.as_raw_fd())?,
+                inner,
+            }),
             Err(error) => Err(error),
         }
     }
 
     pub fn accept(&self, nonblocking: bool) -> io::Result<(TcpStream, SocketAddr)> {
         match self.inner.accept(nonblocking) {
-            Ok((stream, addr)) => Ok((TcpStream { inner: stream }, addr)),
+            Ok((stream, addr)) => Ok((TcpStream::accepted(stream)?, addr)),
             Err(error) => Err(error),
         }
     }
+
+    /// Get local address, e.g. to learn the port picked when binding to
+    /// port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
 }
 
 impl Stream for TcpListener {
@@ -41,33 +239,47 @@ impl Stream for TcpListener {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
+        let coop = match coop::poll_proceed(cx) {
+            Poll::Ready(coop) => coop,
+            Poll::Pending => return Poll::Pending,
+        };
         match self.inner.accept(true) {
-            Ok((stream, addr)) => Poll::Ready(Some(Ok((TcpStream { inner: stream }, addr)))),
+            Ok((stream, addr)) => {
+                coop.made_progress();
+                Poll::Ready(Some(TcpStream::accepted(stream).map(|s| (s, addr))))
+            }
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                EXECUTOR.with(|ex| {
-                    ex.reactor
-                        .borrow_mut()
-                        .modify(self.inner.as_raw_fd(), Interest::Read, cx)
-                });
-                Poll::Pending
+                match self.registration.set_interest(Interest::Read, cx) {
+                    Ok(()) => Poll::Pending,
+                    Err(e) => Poll::Ready(Some(Err(e))),
+                }
+            }
+            Err(e) => {
+                coop.made_progress();
+                std::task::Poll::Ready(Some(Err(e)))
             }
-            Err(e) => std::task::Poll::Ready(Some(Err(e))),
         }
     }
 }
 
 pub struct TcpStream {
-    inner: WasiTcpStream,
+    // See `TcpListener`.
+    registration: Registration,
+    inner: StreamInner,
 }
 
 impl TcpStream {
     pub fn connect<A: ToSocketAddrs>(addrs: A) -> io::Result<TcpStream> {
-        let inner = WasiTcpStream::connect(addrs)?;
-        inner.set_nonblocking(true)?;
-        EXECUTOR.with(|ex| {
-            ex.reactor.borrow_mut().add(inner.as_raw_fd());
-        });
-        Ok(Self { inner })
+        Self::accepted(StreamInner::connect(addrs)?)
+    }
+
+    /// Wraps a stream returned by `accept`, which is registered with the
+    /// reactor like a connected one.
+    fn accepted(inner: StreamInner) -> io::Result<Self> {
+        Ok(Self {
+            registration: Registration::new(inner.as_raw_fd())?,
+            inner,
+        })
     }
 
     fn shutdown(&self, how: Shutdown) -> io::Result<()> {
@@ -91,16 +303,15 @@ impl TcpStream {
     fn poll_write_priv(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
         let this = self;
         match std::io::Write::write(&mut this.inner, buf) {
-            Ok(ret) => Poll::Ready(Ok(ret)),
+            Ok(ret) => {
+                trace!(fd = this.inner.as_raw_fd(), bytes = ret, "tcp write");
+                Poll::Ready(Ok(ret))
+            }
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                EXECUTOR.with(|ex| {
-                    ex.reactor
-                        .borrow_mut()
-                        .modify(this.inner.as_raw_fd(), Interest::Write, cx)
-                });
+                this.registration.set_interest(Interest::Write, cx)?;
                 Poll::Pending
             }
-            Err(e) => return Poll::Ready(Err(e)),
+            Err(e) => Poll::Ready(Err(e)),
         }
     }
 
@@ -115,16 +326,15 @@ impl TcpStream {
                 &mut *(buf.unfilled_mut() as *mut [std::mem::MaybeUninit<u8>] as *mut [u8])
             };
             match std::io::Read::read(&mut this.inner, b) {
-                Ok(ret) => Poll::Ready(Ok(ret)),
+                Ok(ret) => {
+                    trace!(fd = this.inner.as_raw_fd(), bytes = ret, "tcp read");
+                    Poll::Ready(Ok(ret))
+                }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    EXECUTOR.with(|ex| {
-                        ex.reactor
-                            .borrow_mut()
-                            .modify(this.inner.as_raw_fd(), Interest::Read, cx)
-                    });
+                    this.registration.set_interest(Interest::Read, cx)?;
                     Poll::Pending
                 }
-                Err(e) => return Poll::Ready(Err(e)),
+                Err(e) => Poll::Ready(Err(e)),
             }
         };
 
@@ -137,13 +347,7 @@ impl TcpStream {
             buf.assume_init(n);
             buf.advance(n);
         }
-        return Poll::Ready(Ok(()));
-    }
-}
-
-impl Drop for TcpStream {
-    fn drop(&mut self) {
-        EXECUTOR.with(|ex| ex.reactor.borrow_mut().delete(self.inner.as_raw_fd()));
+        Poll::Ready(Ok(()))
     }
 }
 
@@ -153,7 +357,7 @@ impl AsyncRead for TcpStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        self.poll_read_priv(cx, buf)
+        coop::poll_op(cx, |cx| self.poll_read_priv(cx, buf))
     }
 }
 
@@ -163,7 +367,7 @@ impl AsyncWrite for TcpStream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, io::Error>> {
-        self.poll_write_priv(cx, buf)
+        coop::poll_op(cx, |cx| self.poll_write_priv(cx, buf))
     }
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
         Poll::Ready(Ok(()))
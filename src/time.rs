@@ -0,0 +1,103 @@
+//! Timers driven by the reactor.
+
+use crate::executor::{handle, Reactor, TimerKey};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Returns the current time.
+///
+/// Within a [simulation](crate::sim) this is the virtual time of the
+/// simulation, which timers and [`sleep`] follow. Otherwise it is
+/// [`Instant::now`].
+pub fn now() -> Instant {
+    #[cfg(feature = "sim")]
+    if let Some(now) = crate::sim::now() {
+        return now;
+    }
+    Instant::now()
+}
+
+/// Waits until `duration` has elapsed.
+///
+/// # Panics
+///
+/// The returned future panics if it is polled outside of the executor
+/// context.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(now() + duration)
+}
+
+/// Waits until `deadline` is reached.
+///
+/// # Panics
+///
+/// The returned future panics if it is polled outside of the executor
+/// context.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+/// A future returned by [`sleep`] and [`sleep_until`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    deadline: Instant,
+    /// The timer registered with the reactor of the executor polling the
+    /// future.
+    timer: Option<(Weak<Mutex<Reactor>>, TimerKey)>,
+}
+
+impl Sleep {
+    /// Returns the instant at which the future completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    fn remove_timer(&mut self) {
+        if let Some((reactor, key)) = self.timer.take() {
+            if let Some(reactor) = reactor.upgrade() {
+                Reactor::lock(&reactor).timers.remove(key);
+            }
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if now() >= self.deadline {
+            self.remove_timer();
+            return Poll::Ready(());
+        }
+        let reactor = handle::current_reactor();
+        let key = match self.timer.take() {
+            Some((prev, key)) if prev.as_ptr() == Arc::as_ptr(&reactor) => Some(key),
+            // Polled by another executor than before.
+            Some((prev, key)) => {
+                if let Some(prev) = prev.upgrade() {
+                    Reactor::lock(&prev).timers.remove(key);
+                }
+                None
+            }
+            None => None,
+        };
+        let key = Reactor::lock(&reactor)
+            .timers
+            .insert(key, self.deadline, cx);
+        self.timer = Some((Arc::downgrade(&reactor), key));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.remove_timer();
+    }
+}
@@ -0,0 +1,12 @@
+//! Helpers for the optional `tracing` integration.
+//!
+//! The macros expand to nothing unless the `tracing` feature is enabled.
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+pub(crate) use trace;
@@ -0,0 +1,44 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use wasmedge_async::{spawn_local, AsyncReadExt, Executor};
+
+#[test]
+fn task_yields_once_its_budget_is_spent() {
+    let mut executor = Executor::builder().task_budget(4).build();
+    let reads = executor
+        .block_on(|| async {
+            let done = Rc::new(Cell::new(false));
+            let flag = done.clone();
+            // Always-ready reads would keep the task busy forever without the
+            // budget.
+            let reader = spawn_local(async move {
+                let mut reads = 0;
+                let mut buf = [0u8; 4];
+                while !done.get() {
+                    let mut reader: &[u8] = b"data";
+                    reader.read(&mut buf).await.unwrap();
+                    reads += 1;
+                }
+                reads
+            });
+            spawn_local(async move { flag.set(true) });
+            reader.await.unwrap()
+        })
+        .unwrap();
+    // The fifth read yielded, and completed once the other task had run.
+    assert_eq!(reads, 5);
+}
+
+#[test]
+fn zero_task_budget_still_makes_progress() {
+    let mut executor = Executor::builder().task_budget(0).build();
+    let output = executor
+        .block_on(|| async {
+            let mut reader: &[u8] = b"hello";
+            let mut buf = [0u8; 5];
+            let n = reader.read(&mut buf).await.unwrap();
+            buf[..n].to_vec()
+        })
+        .unwrap();
+    assert_eq!(output, b"hello");
+}
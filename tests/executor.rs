@@ -0,0 +1,182 @@
+use futures::channel::oneshot;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::Duration;
+use wasmedge_async::{
+    spawn, spawn_local, spawn_with_permit, time, try_spawn, yield_now, Executor, PanicPolicy,
+};
+
+#[test]
+fn block_on_returns_the_output() {
+    let mut executor = Executor::new();
+    let output = executor.block_on(|| async { 1 + 1 }).unwrap();
+    assert_eq!(output, 2);
+}
+
+#[test]
+fn join_handle_returns_the_task_output() {
+    let mut executor = Executor::new();
+    let output = executor
+        .block_on(|| async {
+            let handles: Vec<_> = (0..10u32)
+                .map(|i| {
+                    spawn(async move {
+                        yield_now().await;
+                        i * 2
+                    })
+                })
+                .collect();
+            let mut sum = 0;
+            for handle in handles {
+                sum += handle.await.unwrap();
+            }
+            sum
+        })
+        .unwrap();
+    assert_eq!(output, 90);
+}
+
+#[test]
+fn join_handle_reports_abort_and_panic() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let handle = spawn(futures::future::pending::<()>());
+            handle.abort();
+            assert!(handle.await.unwrap_err().is_cancelled());
+
+            let handle = spawn(async { panic!("boom") });
+            let err = handle.await.unwrap_err();
+            assert!(err.is_panic());
+            assert_eq!(*err.into_panic().downcast::<&str>().unwrap(), "boom");
+        })
+        .unwrap();
+}
+
+#[test]
+fn detached_task_keeps_running() {
+    let mut executor = Executor::new();
+    let done = Rc::new(Cell::new(false));
+    let flag = done.clone();
+    executor
+        .block_on(move || {
+            let flag = flag.clone();
+            async move {
+                let (tx, rx) = oneshot::channel();
+                drop(spawn_local(async move {
+                    yield_now().await;
+                    flag.set(true);
+                    tx.send(()).unwrap();
+                }));
+                rx.await.unwrap();
+            }
+        })
+        .unwrap();
+    assert!(done.get());
+}
+
+#[test]
+fn executor_can_be_reused() {
+    let mut executor = Executor::new();
+    let (tx, rx) = oneshot::channel::<u32>();
+    let rx = Cell::new(Some(rx));
+    let handle = Rc::new(Cell::new(None));
+    // A task spawned by the first run outlives it and completes in the next.
+    executor
+        .block_on(|| {
+            let rx = rx.take().unwrap();
+            let handle = handle.clone();
+            async move { handle.set(Some(spawn_local(async move { rx.await.unwrap() + 1 }))) }
+        })
+        .unwrap();
+    tx.send(41).unwrap();
+    let output = executor
+        .block_on(|| {
+            let handle = handle.take().unwrap();
+            async move { handle.await.unwrap() }
+        })
+        .unwrap();
+    assert_eq!(output, 42);
+
+    // So do timers.
+    executor
+        .block_on(|| time::sleep(Duration::from_millis(10)))
+        .unwrap();
+}
+
+#[test]
+fn nested_block_on_fails() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let mut inner = Executor::new();
+            assert!(inner.block_on(|| async {}).is_err());
+        })
+        .unwrap();
+}
+
+#[test]
+fn aborting_panic_releases_the_task() {
+    let terminated = Rc::new(Cell::new(0));
+    let counter = terminated.clone();
+    let mut executor = Executor::builder()
+        .panic_policy(PanicPolicy::AbortRuntime)
+        .max_tasks(1)
+        .on_task_terminate(move |_| counter.set(counter.get() + 1))
+        .build();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        executor.block_on(|| async { spawn(async { panic!("boom") }).await })
+    }));
+    assert!(result.is_err());
+    assert_eq!(executor.handle().metrics().live_tasks(), 0);
+    assert_eq!(terminated.get(), 1);
+
+    // The task no longer holds the only slot.
+    executor
+        .block_on(|| async { try_spawn(async {}).unwrap().await.unwrap() })
+        .unwrap();
+}
+
+#[test]
+fn task_hooks_pair_up_when_tasks_are_cancelled() {
+    let spawned = Rc::new(Cell::new(0));
+    let terminated = Rc::new(Cell::new(0));
+    let (s, t) = (spawned.clone(), terminated.clone());
+    let mut executor = Executor::builder()
+        .on_task_spawn(move |_| s.set(s.get() + 1))
+        .on_task_terminate(move |_| t.set(t.get() + 1))
+        .build();
+    executor
+        .block_on(|| async {
+            spawn(async {}).await.unwrap();
+            spawn(futures::future::pending::<()>()).abort();
+            drop(spawn(futures::future::pending::<()>()));
+            yield_now().await;
+        })
+        .unwrap();
+    assert_eq!((spawned.get(), terminated.get()), (3, 2));
+    drop(executor);
+    assert_eq!(terminated.get(), 3);
+}
+
+#[test]
+fn max_tasks_bounds_try_spawn_and_spawn_with_permit() {
+    let mut executor = Executor::builder().max_tasks(2).build();
+    executor
+        .block_on(|| async {
+            let (tx, rx) = oneshot::channel::<()>();
+            let first = try_spawn(async { rx.await.unwrap() }).unwrap();
+            let _second = try_spawn(futures::future::pending::<()>()).unwrap();
+            let err = try_spawn(async {}).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+            // Waits for a slot, which frees up once the first task completes.
+            let mut third = Box::pin(spawn_with_permit(async { 3 }));
+            assert!(futures::poll!(third.as_mut()).is_pending());
+            tx.send(()).unwrap();
+            first.await.unwrap();
+            assert_eq!(third.await.await.unwrap(), 3);
+        })
+        .unwrap();
+}
@@ -0,0 +1,109 @@
+use futures::future::pending;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use wasmedge_async::{spawn, task, time, yield_now, Executor, Handle, TaskState};
+
+#[test]
+fn metrics_count_tasks_and_polls() {
+    let mut executor = Executor::builder().enable_poll_time_histogram().build();
+    executor
+        .block_on(|| async {
+            let handles: Vec<_> = (0..3).map(|_| spawn(yield_now())).collect();
+            let idle = spawn(pending::<()>());
+            for handle in handles {
+                handle.await.unwrap();
+            }
+
+            let metrics = Handle::current().metrics();
+            assert_eq!(metrics.spawned_tasks(), 4);
+            assert_eq!(metrics.completed_tasks(), 3);
+            assert_eq!(metrics.live_tasks(), 1);
+            assert_eq!(metrics.task_polls(), 7);
+            assert_eq!(metrics.queue_depth(), 0);
+            assert_eq!(metrics.queue_high_water_mark(), 4);
+            let histogram = metrics.poll_time_histogram().unwrap();
+            let recorded: u64 = histogram.buckets().map(|(_, count)| count).sum();
+            assert_eq!(recorded, metrics.task_polls());
+            idle.abort();
+            assert!(idle.await.unwrap_err().is_cancelled());
+        })
+        .unwrap();
+    let metrics = executor.handle().metrics();
+    assert_eq!(metrics.completed_tasks(), 4);
+    assert_eq!(metrics.live_tasks(), 0);
+}
+
+#[test]
+fn dump_shows_what_tasks_wait_on() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let sleeper = task::Builder::new()
+                .name("sleeper")
+                .spawn(time::sleep(Duration::from_secs(10)));
+            let idle = spawn(pending::<()>());
+            yield_now().await;
+            let queued = spawn(async {});
+
+            let dump = Handle::current().dump();
+            let tasks = dump.tasks();
+            assert_eq!(tasks.len(), 3);
+            assert_eq!(tasks[0].info().name(), Some("sleeper"));
+            assert!(matches!(tasks[0].state(), TaskState::Parked(_)));
+            assert_eq!(tasks[1].state(), &TaskState::Idle);
+            assert_eq!(tasks[2].state(), &TaskState::Queued);
+
+            let output = dump.to_string();
+            let lines: Vec<_> = output.lines().collect();
+            assert!(
+                lines[0].starts_with(&format!(
+                    "sleeper (task {}): parked on timer in ",
+                    tasks[0].info().id()
+                )),
+                "{}",
+                output
+            );
+            assert_eq!(lines[1], format!("task {}: idle", tasks[1].info().id()));
+            assert_eq!(lines[2], format!("task {}: queued", tasks[2].info().id()));
+
+            // The task taking the dump is running.
+            let running = spawn(async {
+                let dump = Handle::current().dump();
+                let me = task::current().id();
+                dump.tasks()
+                    .iter()
+                    .find(|task| task.info().id() == me)
+                    .map(|task| task.state().clone())
+            });
+            assert_eq!(running.await.unwrap(), Some(TaskState::Running));
+
+            sleeper.abort();
+            idle.abort();
+            queued.await.unwrap();
+        })
+        .unwrap();
+}
+
+#[test]
+fn slow_polls_are_reported() {
+    let slow = Rc::new(RefCell::new(Vec::new()));
+    let reported = slow.clone();
+    let mut executor = Executor::builder()
+        .on_slow_poll(Duration::from_millis(20), move |task, elapsed| {
+            assert!(elapsed >= Duration::from_millis(20));
+            reported.borrow_mut().push(task.name().map(String::from));
+        })
+        .build();
+    executor
+        .block_on(|| async {
+            task::Builder::new()
+                .name("blocker")
+                .spawn(async { std::thread::sleep(Duration::from_millis(30)) })
+                .await
+                .unwrap();
+            spawn(async {}).await.unwrap();
+        })
+        .unwrap();
+    assert_eq!(*slow.borrow(), [Some("blocker".to_string())]);
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "multi-thread")]
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmedge_async::multi_thread::Runtime;
+use wasmedge_async::{spawn, spawn_blocking, spawn_local, time, Executor};
+
+#[test]
+fn runtime_runs_tasks_on_its_workers() {
+    let runtime = Runtime::builder().worker_threads(4).build().unwrap();
+    let completed = Arc::new(AtomicUsize::new(0));
+    let counter = completed.clone();
+    let total = runtime
+        .block_on(move || async move {
+            let handles: Vec<_> = (0..200usize)
+                .map(|i| {
+                    let counter = counter.clone();
+                    spawn(async move {
+                        time::sleep(Duration::from_millis((i % 5) as u64)).await;
+                        let doubled = spawn(async move { i * 2 }).await.unwrap();
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        doubled
+                    })
+                })
+                .collect();
+            let mut total = 0;
+            for handle in handles {
+                total += handle.await.unwrap();
+            }
+            total
+        })
+        .unwrap();
+    assert_eq!(total, (0..200).map(|i| i * 2).sum::<usize>());
+    assert_eq!(completed.load(Ordering::SeqCst), 200);
+
+    let err = runtime
+        .block_on(|| runtime.spawn(async { panic!("boom") }))
+        .unwrap()
+        .unwrap_err();
+    assert!(err.is_panic());
+}
+
+#[test]
+fn idle_workers_steal_tasks() {
+    let runtime = Runtime::builder().worker_threads(4).build().unwrap();
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    runtime
+        .block_on(|| {
+            let seen = threads.clone();
+            // Spawned from a task, the children all go to that worker's queue.
+            runtime.spawn(async move {
+                let handles: Vec<_> = (0..16)
+                    .map(|_| {
+                        let seen = seen.clone();
+                        spawn(async move {
+                            seen.lock().unwrap().insert(std::thread::current().id());
+                            std::thread::sleep(Duration::from_millis(10));
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+            })
+        })
+        .unwrap()
+        .unwrap();
+    assert!(threads.lock().unwrap().len() > 1);
+}
+
+#[test]
+fn spawn_blocking_wakes_an_executor_task() {
+    let mut executor = Executor::new();
+    let outputs = executor
+        .block_on(|| async {
+            let slow = spawn_local(async {
+                spawn_blocking(|| {
+                    std::thread::sleep(Duration::from_millis(20));
+                    1
+                })
+                .await
+                .unwrap()
+            });
+            let fast = spawn(async { spawn_blocking(|| 2).await.unwrap() });
+            let panicked = spawn_blocking(|| panic!("boom"));
+            (
+                slow.await.unwrap(),
+                fast.await.unwrap(),
+                panicked.await.unwrap_err().is_panic(),
+            )
+        })
+        .unwrap();
+    assert_eq!(outputs, (1, 2, true));
+}
+
+#[test]
+fn spawn_blocking_wakes_a_runtime_task() {
+    let runtime = Runtime::builder().worker_threads(2).build().unwrap();
+    let output = runtime
+        .block_on(|| async { spawn(async { spawn_blocking(|| 5).await.unwrap() }).await })
+        .unwrap();
+    assert_eq!(output.unwrap(), 5);
+}
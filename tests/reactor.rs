@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+use wasmedge_async::Executor;
+
+#[cfg(not(target_os = "wasi"))]
+#[test]
+fn reactor_wakes_on_readiness() {
+    use futures::task::{waker, ArcWake};
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::Context;
+    use wasmedge_async::{Interest, Reactor};
+
+    #[derive(Default)]
+    struct Flag(AtomicBool);
+
+    impl ArcWake for Flag {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let (reader, mut writer) = UnixStream::pair().unwrap();
+    reader.set_nonblocking(true).unwrap();
+    let mut reactor = Reactor::new();
+    assert_eq!(reactor.registered_fds(), 0);
+    reactor.add(reader.as_raw_fd()).unwrap();
+    assert_eq!(reactor.registered_fds(), 1);
+    let flag = Arc::new(Flag::default());
+    let waker = waker(flag.clone());
+    reactor.modify(
+        reader.as_raw_fd(),
+        Interest::Read,
+        &mut Context::from_waker(&waker),
+    );
+
+    reactor.wait_timeout(Duration::from_millis(10)).unwrap();
+    assert!(!flag.0.load(Ordering::SeqCst));
+
+    writer.write_all(b"x").unwrap();
+    reactor.wait_timeout(Duration::from_secs(5)).unwrap();
+    assert!(flag.0.load(Ordering::SeqCst));
+
+    reactor.delete(reader.as_raw_fd());
+    assert_eq!(reactor.registered_fds(), 0);
+}
+
+#[test]
+fn idle_executor_has_no_registered_fds() {
+    let executor = Executor::new();
+    assert_eq!(executor.handle().metrics().registered_fds(), 0);
+}
+
+#[test]
+fn timer_wakes_a_waiting_executor() {
+    let mut executor = Executor::new();
+    let start = Instant::now();
+    executor
+        .block_on(|| wasmedge_async::time::sleep(Duration::from_millis(50)))
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+/// A task woken from another thread runs while the executor waits for IO
+/// with no timer set.
+#[cfg(not(target_os = "wasi"))]
+#[test]
+fn remote_wake_interrupts_the_reactor() {
+    use futures::channel::oneshot;
+    use wasmedge_async::spawn;
+
+    let mut executor = Executor::new();
+    let start = Instant::now();
+    let output = executor
+        .block_on(|| async {
+            let (tx, rx) = oneshot::channel();
+            let handle = spawn(async move { rx.await.unwrap() });
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                tx.send(7).unwrap();
+            });
+            handle.await.unwrap()
+        })
+        .unwrap();
+    assert_eq!(output, 7);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,139 @@
+use futures::channel::{mpsc, oneshot};
+use futures::StreamExt;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasmedge_async::task::{self, Priority};
+use wasmedge_async::{spawn_local, yield_now, Executor};
+
+#[test]
+fn higher_priorities_run_first() {
+    let mut executor = Executor::new();
+    let order = Rc::new(RefCell::new(Vec::new()));
+    executor
+        .block_on(|| {
+            let order = order.clone();
+            async move {
+                let priorities = [
+                    Priority::Low,
+                    Priority::Normal,
+                    Priority::High,
+                    Priority::Low,
+                    Priority::High,
+                ];
+                let handles: Vec<_> = priorities
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, priority)| {
+                        let order = order.clone();
+                        task::Builder::new()
+                            .priority(priority)
+                            .spawn_local(async move { order.borrow_mut().push(i) })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+            }
+        })
+        .unwrap();
+    assert_eq!(*order.borrow(), [2, 4, 1, 0, 3]);
+}
+
+#[test]
+fn low_priority_is_not_starved() {
+    let mut executor = Executor::new();
+    let polls = Rc::new(Cell::new(0));
+    let seen = executor
+        .block_on(|| {
+            let polls = polls.clone();
+            async move {
+                for _ in 0..4 {
+                    let polls = polls.clone();
+                    task::Builder::new()
+                        .priority(Priority::High)
+                        .spawn_local(async move {
+                            loop {
+                                polls.set(polls.get() + 1);
+                                yield_now().await;
+                            }
+                        });
+                }
+                task::Builder::new()
+                    .priority(Priority::Low)
+                    .spawn_local(async move { polls.get() })
+                    .await
+                    .unwrap()
+            }
+        })
+        .unwrap();
+    // A level is served after being passed over 8 times.
+    assert_eq!(seen, 8);
+}
+
+#[test]
+fn woken_task_runs_next() {
+    let mut executor = Executor::new();
+    let order = Rc::new(RefCell::new(Vec::new()));
+    executor
+        .block_on(|| {
+            let order = order.clone();
+            async move {
+                let (tx, rx) = oneshot::channel();
+                let log = order.clone();
+                let woken = spawn_local(async move {
+                    rx.await.unwrap();
+                    log.borrow_mut().push("woken");
+                });
+                yield_now().await;
+
+                let log = order.clone();
+                let waker = spawn_local(async move {
+                    tx.send(()).unwrap();
+                    log.borrow_mut().push("waker");
+                });
+                let log = order.clone();
+                let queued = spawn_local(async move { log.borrow_mut().push("queued") });
+                for handle in [woken, waker, queued] {
+                    handle.await.unwrap();
+                }
+            }
+        })
+        .unwrap();
+    assert_eq!(*order.borrow(), ["waker", "woken", "queued"]);
+}
+
+#[test]
+fn tasks_waking_each_other_do_not_starve_the_queue() {
+    const ROUNDS: usize = 100;
+
+    let mut executor = Executor::new();
+    let rounds = Rc::new(Cell::new(0));
+    let seen = executor
+        .block_on(|| {
+            let rounds = rounds.clone();
+            async move {
+                let (ping_tx, mut ping_rx) = mpsc::unbounded();
+                let (pong_tx, mut pong_rx) = mpsc::unbounded();
+                let pinger = spawn_local(async move {
+                    for _ in 0..ROUNDS {
+                        ping_tx.unbounded_send(()).unwrap();
+                        pong_rx.next().await.unwrap();
+                    }
+                });
+                let counter = rounds.clone();
+                let ponger = spawn_local(async move {
+                    while ping_rx.next().await.is_some() {
+                        counter.set(counter.get() + 1);
+                        pong_tx.unbounded_send(()).unwrap();
+                    }
+                });
+                let seen = spawn_local(async move { rounds.get() }).await.unwrap();
+                pinger.await.unwrap();
+                ponger.await.unwrap();
+                seen
+            }
+        })
+        .unwrap();
+    assert!(seen < 10, "{}", seen);
+    assert_eq!(rounds.get(), ROUNDS);
+}
@@ -0,0 +1,121 @@
+use futures::future::{pending, FutureExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use wasmedge_async::{scope, yield_now, Executor};
+
+#[test]
+fn children_borrow_from_the_parent() {
+    let mut executor = Executor::new();
+    let data = executor
+        .block_on(|| async {
+            let mut data = vec![1, 2, 3];
+            let sum = scope(|s| {
+                let data = &mut data;
+                async move {
+                    let handles: Vec<_> = data
+                        .iter_mut()
+                        .map(|x| {
+                            s.spawn(async move {
+                                yield_now().await;
+                                *x *= 10;
+                                *x
+                            })
+                        })
+                        .collect();
+                    let mut sum = 0;
+                    for handle in handles {
+                        sum += handle.await.unwrap();
+                    }
+                    sum
+                }
+            })
+            .await;
+            assert_eq!(sum, 60);
+            data
+        })
+        .unwrap();
+    assert_eq!(data, [10, 20, 30]);
+}
+
+#[test]
+fn dropping_the_scope_cancels_children() {
+    struct SetOnDrop<'a>(&'a AtomicBool);
+
+    impl Drop for SetOnDrop<'_> {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let dropped = AtomicBool::new(false);
+            let scope = scope(|s| {
+                let dropped = &dropped;
+                async move {
+                    s.spawn(async move {
+                        let _guard = SetOnDrop(dropped);
+                        pending::<()>().await
+                    });
+                    pending::<()>().await
+                }
+            });
+            // Polls the body, which spawns the child, and the child once.
+            assert!(scope.now_or_never().is_none());
+            assert!(dropped.load(Ordering::SeqCst));
+        })
+        .unwrap();
+}
+
+#[test]
+fn scope_waits_for_children_spawned_after_the_body_returns() {
+    let mut executor = Executor::new();
+    let log = executor
+        .block_on(|| async {
+            let log = Mutex::new(Vec::new());
+            scope(|s| {
+                let log = &log;
+                let inner = s.clone();
+                async move {
+                    s.spawn(async move {
+                        yield_now().await;
+                        inner.spawn(async move {
+                            yield_now().await;
+                            log.lock().unwrap().push("grandchild");
+                        });
+                        log.lock().unwrap().push("child");
+                    });
+                    log.lock().unwrap().push("body");
+                }
+            })
+            .await;
+            log.into_inner().unwrap()
+        })
+        .unwrap();
+    assert_eq!(log, ["body", "child", "grandchild"]);
+}
+
+#[cfg(feature = "multi-thread")]
+#[test]
+fn scope_runs_in_a_multi_threaded_task() {
+    use wasmedge_async::multi_thread::Runtime;
+
+    let runtime = Runtime::new().unwrap();
+    let output = runtime
+        .block_on(|| {
+            runtime.spawn(async {
+                let data = [1, 2, 3];
+                scope(|s| {
+                    let data = &data;
+                    async move {
+                        let handle = s.spawn(async move { data.iter().sum::<i32>() });
+                        handle.await.unwrap()
+                    }
+                })
+                .await
+            })
+        })
+        .unwrap();
+    assert_eq!(output.unwrap(), 6);
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "sim")]
+
+use futures::StreamExt;
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+use wasmedge_async::sim::{self, Sim};
+use wasmedge_async::{spawn_local, time, AsyncReadExt, AsyncWriteExt, TcpListener, TcpStream};
+
+const SERVER: [u8; 4] = [10, 0, 0, 1];
+const CLIENT: [u8; 4] = [10, 0, 0, 2];
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+/// Echoes every connection to port 80, logging what it receives.
+async fn serve(log: Log) {
+    let mut listener = TcpListener::bind("0.0.0.0:80", true).unwrap();
+    while let Some(Ok((mut stream, peer))) = listener.next().await {
+        let log = log.clone();
+        spawn_local(async move {
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                log.borrow_mut().push(format!(
+                    "{} from {}",
+                    String::from_utf8_lossy(&buf[..n]),
+                    peer
+                ));
+                let mut written = 0;
+                while written < n {
+                    written += stream.write(&buf[written..n]).await.unwrap();
+                }
+            }
+        });
+    }
+}
+
+async fn echo(msg: &[u8]) -> io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect("10.0.0.1:80")?;
+    let mut written = 0;
+    while written < msg.len() {
+        written += stream.write(&msg[written..]).await?;
+    }
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 1024];
+    while reply.len() < msg.len() {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+    Ok(reply)
+}
+
+/// Runs clients racing each other over a lossy network, returning the log of
+/// the run and how long it took.
+fn run(seed: u64) -> (Vec<String>, Duration) {
+    let mut sim = Sim::builder()
+        .seed(seed)
+        .latency(Duration::from_millis(1), Duration::from_millis(50))
+        .packet_loss(0.1)
+        .build();
+    let log = Log::default();
+    let server_log = log.clone();
+    sim.spawn(SERVER, move || serve(server_log));
+    for i in 0..8 {
+        let log = log.clone();
+        sim.spawn(CLIENT, move || async move {
+            let msg = format!("client {}", i);
+            let reply = echo(msg.as_bytes()).await.unwrap();
+            assert_eq!(reply, msg.as_bytes());
+            log.borrow_mut().push(format!("{} done", msg));
+        });
+    }
+    sim.block_on(CLIENT, || time::sleep(Duration::from_secs(10)))
+        .unwrap();
+    let log = log.borrow().clone();
+    (log, sim.elapsed())
+}
+
+#[test]
+fn same_seed_same_run() {
+    let (log, elapsed) = run(7);
+    assert_eq!(log.iter().filter(|e| e.ends_with("done")).count(), 8);
+    assert_eq!(run(7), (log, elapsed));
+}
+
+#[test]
+fn partition_times_out_connects_until_healed() {
+    let mut sim = Sim::new(0);
+    sim.spawn(SERVER, || serve(Log::default()));
+    sim.partition(SERVER, CLIENT);
+
+    let start = sim.elapsed();
+    let err = sim.block_on(CLIENT, || echo(b"ping")).unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    let waited = sim.elapsed() - start;
+    assert!(waited >= Duration::from_secs(3), "{:?}", waited);
+    assert!(waited < Duration::from_secs(4), "{:?}", waited);
+
+    sim.heal(SERVER, CLIENT);
+    let reply = sim.block_on(CLIENT, || echo(b"ping")).unwrap().unwrap();
+    assert_eq!(reply, b"ping");
+
+    // Segments of an established connection are held back by a partition,
+    // and arrive once it heals.
+    sim.spawn([10, 0, 0, 3], || async {
+        time::sleep(Duration::from_secs(5)).await;
+        sim::heal(SERVER, CLIENT);
+    });
+    let start = sim.elapsed();
+    let reply = sim
+        .block_on(CLIENT, || async {
+            let mut stream = TcpStream::connect("10.0.0.1:80")?;
+            stream.write(b"ping").await?;
+            stream.read(&mut [0u8; 4]).await?;
+            sim::partition(SERVER, CLIENT);
+            stream.write(b"pong").await?;
+            let mut buf = [0u8; 4];
+            let n = stream.read(&mut buf).await?;
+            Ok::<_, io::Error>(buf[..n].to_vec())
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply, b"pong");
+    assert!(sim.elapsed() - start >= Duration::from_secs(5));
+}
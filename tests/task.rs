@@ -0,0 +1,109 @@
+use futures::channel::oneshot;
+use futures::future::pending;
+use wasmedge_async::task::{self, JoinSet};
+use wasmedge_async::{spawn, yield_now, Executor};
+
+wasmedge_async::task_local! {
+    static REQUEST_ID: u64;
+}
+
+#[test]
+fn join_set_returns_outputs_in_completion_order() {
+    let mut executor = Executor::new();
+    let order = executor
+        .block_on(|| async {
+            let mut set = JoinSet::new();
+            for i in 0..5u32 {
+                set.spawn(async move {
+                    for _ in 0..(5 - i) {
+                        yield_now().await;
+                    }
+                    i
+                });
+            }
+            assert_eq!(set.len(), 5);
+            let mut order = Vec::new();
+            while let Some(output) = set.join_next().await {
+                order.push(output.unwrap());
+            }
+            assert!(set.is_empty());
+            order
+        })
+        .unwrap();
+    assert_eq!(order, [4, 3, 2, 1, 0]);
+}
+
+#[test]
+fn join_set_abort_all_cancels_its_tasks() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let mut set = JoinSet::new();
+            set.spawn(pending::<()>());
+            set.abort_all();
+            assert!(set.join_next().await.unwrap().unwrap_err().is_cancelled());
+            assert!(set.join_next().await.is_none());
+        })
+        .unwrap();
+}
+
+#[test]
+fn dropping_a_join_set_aborts_its_tasks() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let (tx, rx) = oneshot::channel::<()>();
+            let mut set = JoinSet::new();
+            set.spawn(async move {
+                let _tx = tx;
+                pending::<()>().await
+            });
+            yield_now().await;
+            drop(set);
+            // Cancelling the task drops the sender.
+            assert!(rx.await.is_err());
+        })
+        .unwrap();
+}
+
+#[test]
+fn task_local_follows_its_future() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let a = spawn(REQUEST_ID.scope(1, async {
+                yield_now().await;
+                REQUEST_ID.get()
+            }));
+            let b = spawn(REQUEST_ID.scope(2, async {
+                yield_now().await;
+                REQUEST_ID.get()
+            }));
+            assert_eq!((a.await.unwrap(), b.await.unwrap()), (1, 2));
+
+            let nested = REQUEST_ID.scope(3, async {
+                let inner = REQUEST_ID.scope(4, async { REQUEST_ID.get() }).await;
+                (inner, REQUEST_ID.get())
+            });
+            assert_eq!(nested.await, (4, 3));
+
+            assert!(REQUEST_ID.try_with(|_| ()).is_err());
+            assert_eq!(REQUEST_ID.sync_scope(5, || REQUEST_ID.get()), 5);
+        })
+        .unwrap();
+}
+
+#[test]
+fn named_task_sees_its_name() {
+    let mut executor = Executor::new();
+    let name = executor
+        .block_on(|| async {
+            task::Builder::new()
+                .name("worker")
+                .spawn(async { task::current().name().map(String::from) })
+                .await
+                .unwrap()
+        })
+        .unwrap();
+    assert_eq!(name.as_deref(), Some("worker"));
+}
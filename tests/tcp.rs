@@ -0,0 +1,95 @@
+use futures::StreamExt;
+use std::time::Duration;
+use wasmedge_async::{
+    spawn_local, time, AsyncReadExt, AsyncWriteExt, Executor, TcpListener, TcpStream,
+};
+
+#[test]
+fn loopback_echo() {
+    let mut executor = Executor::new();
+    let replies = executor
+        .block_on(|| async {
+            let mut listener = TcpListener::bind("127.0.0.1:0", true).unwrap();
+            let addr = listener.local_addr().unwrap();
+            assert_ne!(addr.port(), 0);
+            spawn_local(async move {
+                while let Some(Ok((mut stream, _))) = listener.next().await {
+                    spawn_local(async move {
+                        let mut buf = [0u8; 1024];
+                        loop {
+                            let n = stream.read(&mut buf).await.unwrap();
+                            if n == 0 {
+                                break;
+                            }
+                            let mut written = 0;
+                            while written < n {
+                                written += stream.write(&buf[written..n]).await.unwrap();
+                            }
+                        }
+                    });
+                }
+            });
+
+            let clients: Vec<_> = (0..4)
+                .map(|i| {
+                    spawn_local(async move {
+                        let mut stream = TcpStream::connect(addr).unwrap();
+                        assert_eq!(stream.peer_addr().unwrap(), addr);
+                        let msg = format!("hello {}", i).repeat(1000);
+                        let mut written = 0;
+                        while written < msg.len() {
+                            written += stream.write(&msg.as_bytes()[written..]).await.unwrap();
+                        }
+                        let mut reply = Vec::new();
+                        let mut buf = [0u8; 1024];
+                        while reply.len() < msg.len() {
+                            let n = stream.read(&mut buf).await.unwrap();
+                            assert!(n > 0, "connection closed early");
+                            reply.extend_from_slice(&buf[..n]);
+                        }
+                        reply == msg.as_bytes()
+                    })
+                })
+                .collect();
+            let mut replies = Vec::new();
+            for client in clients {
+                replies.push(client.await.unwrap());
+            }
+            replies
+        })
+        .unwrap();
+    assert_eq!(replies, [true; 4]);
+}
+
+#[test]
+fn blocking_listener_does_not_block_the_executor() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let mut listener = TcpListener::bind("127.0.0.1:0", false).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let accepted = spawn_local(async move { listener.next().await.unwrap().unwrap().1 });
+            // Timers keep firing while the listener waits for a connection.
+            time::sleep(Duration::from_millis(20)).await;
+            let stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(accepted.await.unwrap(), stream.local_addr().unwrap());
+        })
+        .unwrap();
+}
+
+#[test]
+fn blocking_accept_waits_for_a_connection() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let listener = TcpListener::bind("127.0.0.1:0", true).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let client = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                std::net::TcpStream::connect(addr).unwrap()
+            });
+            let (_stream, peer) = listener.accept(false).unwrap();
+            assert_eq!(peer, client.join().unwrap().local_addr().unwrap());
+        })
+        .unwrap();
+}
@@ -0,0 +1,93 @@
+use futures::future::{pending, select};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use wasmedge_async::{spawn_local, time, yield_now, Executor, Handle, TaskState, WaitingOn};
+
+#[test]
+fn sleep_waits_for_the_duration() {
+    let mut executor = Executor::new();
+    let start = Instant::now();
+    executor
+        .block_on(|| {
+            let sleep = time::sleep(Duration::from_millis(30));
+            assert!(sleep.deadline() >= start + Duration::from_millis(30));
+            sleep
+        })
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+fn timers_fire_in_deadline_order() {
+    let mut executor = Executor::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    executor
+        .block_on(|| {
+            let log = log.clone();
+            async move {
+                let start = time::now();
+                let handles: Vec<_> = [30, 10, 20]
+                    .into_iter()
+                    .map(|ms| {
+                        let log = log.clone();
+                        spawn_local(async move {
+                            time::sleep_until(start + Duration::from_millis(ms)).await;
+                            log.borrow_mut().push(ms);
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+            }
+        })
+        .unwrap();
+    assert_eq!(*log.borrow(), [10, 20, 30]);
+}
+
+#[test]
+fn dropping_a_sleep_cancels_its_timer() {
+    let mut executor = Executor::new();
+    let start = Instant::now();
+    executor
+        .block_on(|| async {
+            // The long sleep loses the race and is dropped.
+            select(
+                time::sleep(Duration::from_secs(10)),
+                time::sleep(Duration::from_millis(10)),
+            )
+            .await;
+
+            let handle = spawn_local(async {
+                let mut sleep = time::sleep(Duration::from_secs(10));
+                assert!(futures::poll!(&mut sleep).is_pending());
+                drop(sleep);
+                pending::<()>().await
+            });
+            yield_now().await;
+            let dump = Handle::current().dump();
+            assert_eq!(dump.tasks()[0].state(), &TaskState::Idle);
+            handle.abort();
+        })
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn pending_sleep_shows_in_the_dump() {
+    let mut executor = Executor::new();
+    executor
+        .block_on(|| async {
+            let deadline = time::now() + Duration::from_secs(10);
+            let handle = spawn_local(time::sleep_until(deadline));
+            yield_now().await;
+            let dump = Handle::current().dump();
+            assert_eq!(
+                dump.tasks()[0].state(),
+                &TaskState::Parked(vec![WaitingOn::Timer(deadline)])
+            );
+            handle.abort();
+        })
+        .unwrap();
+}